@@ -0,0 +1,26 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use maiq_parser::{env, parser::parse_snapshot, utils::time::now_date, Num};
+use tl_table_parser::Table;
+
+fuzz_target!(
+  init: {
+    std::env::set_var("GROUPS", "Ит1-22; Ир3-21; С3-20");
+  },
+  |rows: Vec<Vec<String>>| {
+    let known: Vec<String> = env::groups().into();
+    let snapshot = match parse_snapshot(Table { rows }, now_date()) {
+      Ok(snapshot) => snapshot,
+      Err(_) => return,
+    };
+
+    for group in snapshot.groups.iter() {
+      assert!(known.contains(&group.name), "unknown group {}", group.name);
+      for lesson in group.lessons.iter() {
+        assert!(!matches!(lesson.num, Num::Actual(ref x) if x.is_empty() || x.contains(',')), "{:?}", lesson.num);
+      }
+      assert!(group.lessons.windows(2).all(|w| w[0].num <= w[1].num));
+    }
+  }
+);
@@ -0,0 +1,54 @@
+use chrono::{DateTime, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{DayKind, Num};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Bell {
+  pub num: String,
+  pub start: NaiveTime,
+  pub end: NaiveTime,
+}
+
+impl Bell {
+  pub fn contains(&self, time: NaiveTime) -> bool {
+    self.start <= time && time < self.end
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct BellSchedule {
+  pub bells: Vec<Bell>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub shortened: Vec<Bell>,
+}
+
+impl BellSchedule {
+  pub fn new(bells: Vec<Bell>) -> Self {
+    Self { bells, shortened: vec![] }
+  }
+
+  /// Bells that apply on a day of the given kind. Shortened days fall back to the regular
+  /// bells when no shortened schedule is known, holidays have no bells at all.
+  pub fn for_day(&self, kind: DayKind) -> BellSchedule {
+    let bells = match kind {
+      DayKind::Shortened if !self.shortened.is_empty() => self.shortened.clone(),
+      DayKind::Holiday => vec![],
+      _ => self.bells.clone(),
+    };
+    BellSchedule::new(bells)
+  }
+
+  pub fn get(&self, num: &Num) -> Option<&Bell> {
+    match num {
+      Num::Actual(num) => self.bells.iter().find(|b| b.num == *num),
+      _ => None,
+    }
+  }
+
+  pub fn at(&self, time: DateTime<Utc>) -> Option<&Bell> {
+    self.bells.iter().find(|b| b.contains(time.time()))
+  }
+}
@@ -0,0 +1,221 @@
+//! CBOR encoding of snapshots for storage and IPC.
+//!
+//! Every payload starts with a header: `MAIQ`, a format byte and the little-endian schema version it was
+//! written with. Decoding goes through [`schema::from_value`], so older payloads are migrated the same
+//! way old JSON dumps are.
+//!
+//! Histories intern every string, map keys included, into a single dictionary stored in front of the
+//! snapshots. A reference is a one-element array holding the dictionary index: snapshots have no lists of
+//! integers, and unlike the registered stringref tag (25) it does not claim a CBOR extension we don't follow.
+
+use std::{collections::HashMap, fmt::Display};
+
+use ciborium::value::Value;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+  schema::{self, SchemaError, SCHEMA_VERSION},
+  Snapshot,
+};
+
+const MAGIC: &[u8; 4] = b"MAIQ";
+const HEADER_LEN: usize = MAGIC.len() + 1 + 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Format {
+  Snapshot = 1,
+  History = 2,
+}
+
+#[derive(Debug)]
+pub enum BinaryError {
+  Header,
+  UnexpectedFormat(u8),
+  Cbor(String),
+  Schema(SchemaError),
+}
+
+impl Display for BinaryError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      BinaryError::Header => f.write_str("Missing or broken header"),
+      BinaryError::UnexpectedFormat(x) => write!(f, "Unexpected payload format {}", x),
+      BinaryError::Cbor(e) => write!(f, "Unable to decode CBOR: {}", e),
+      BinaryError::Schema(e) => e.fmt(f),
+    }
+  }
+}
+
+impl std::error::Error for BinaryError {}
+
+impl From<SchemaError> for BinaryError {
+  fn from(value: SchemaError) -> Self {
+    BinaryError::Schema(value)
+  }
+}
+
+impl Snapshot {
+  pub fn to_bytes(&self) -> Vec<u8> {
+    encode(Format::Snapshot, self)
+  }
+
+  pub fn from_bytes(bytes: &[u8]) -> Result<Self, BinaryError> {
+    let value = decode::<Value>(Format::Snapshot, bytes)?;
+    migrate(value)
+  }
+}
+
+pub fn history_to_bytes(snapshots: &[Snapshot]) -> Vec<u8> {
+  let mut dictionary = Dictionary::default();
+  let snapshots = snapshots
+    .iter()
+    .map(|s| dictionary.intern(Value::serialized(s).expect("Snapshot is always representable in CBOR")))
+    .collect::<Vec<Value>>();
+  encode(Format::History, &(dictionary.strings, snapshots))
+}
+
+pub fn history_from_bytes(bytes: &[u8]) -> Result<Vec<Snapshot>, BinaryError> {
+  let (strings, snapshots) = decode::<(Vec<String>, Vec<Value>)>(Format::History, bytes)?;
+  snapshots
+    .into_iter()
+    .map(|s| resolve(s, &strings).and_then(migrate))
+    .collect()
+}
+
+fn encode<T: Serialize>(format: Format, value: &T) -> Vec<u8> {
+  let mut bytes = Vec::with_capacity(HEADER_LEN);
+  bytes.extend_from_slice(MAGIC);
+  bytes.push(format as u8);
+  bytes.extend_from_slice(&SCHEMA_VERSION.to_le_bytes());
+  ciborium::ser::into_writer(value, &mut bytes).expect("Writing into a Vec can't fail");
+  bytes
+}
+
+fn decode<T: DeserializeOwned>(format: Format, bytes: &[u8]) -> Result<T, BinaryError> {
+  if bytes.len() < HEADER_LEN || &bytes[..MAGIC.len()] != MAGIC {
+    return Err(BinaryError::Header);
+  }
+
+  match bytes[MAGIC.len()] {
+    x if x == format as u8 => (),
+    x => return Err(BinaryError::UnexpectedFormat(x)),
+  }
+
+  let version = u32::from_le_bytes(bytes[MAGIC.len() + 1..HEADER_LEN].try_into().unwrap());
+  if version > SCHEMA_VERSION {
+    return Err(SchemaError::Unsupported(version).into());
+  }
+
+  ciborium::de::from_reader(&bytes[HEADER_LEN..]).map_err(|e| BinaryError::Cbor(e.to_string()))
+}
+
+fn migrate(value: Value) -> Result<Snapshot, BinaryError> {
+  let json = value
+    .deserialized::<serde_json::Value>()
+    .map_err(|e| BinaryError::Cbor(e.to_string()))?;
+  Ok(schema::from_value(json)?)
+}
+
+#[derive(Default)]
+struct Dictionary {
+  strings: Vec<String>,
+  indices: HashMap<String, u64>,
+}
+
+impl Dictionary {
+  fn intern(&mut self, value: Value) -> Value {
+    match value {
+      Value::Text(text) => {
+        let next = self.strings.len() as u64;
+        let index = *self.indices.entry(text.clone()).or_insert(next);
+        if index == next {
+          self.strings.push(text);
+        }
+        Value::Array(vec![Value::Integer(index.into())])
+      }
+      Value::Array(items) => Value::Array(items.into_iter().map(|x| self.intern(x)).collect()),
+      Value::Map(entries) => Value::Map(
+        entries
+          .into_iter()
+          .map(|(k, v)| (self.intern(k), self.intern(v)))
+          .collect(),
+      ),
+      Value::Tag(tag, inner) => Value::Tag(tag, Box::new(self.intern(*inner))),
+      other => other,
+    }
+  }
+}
+
+fn resolve(value: Value, strings: &[String]) -> Result<Value, BinaryError> {
+  Ok(match value {
+    Value::Array(items) if matches!(items.as_slice(), [Value::Integer(_)]) => {
+      let index = match items[0] {
+        Value::Integer(x) => u64::try_from(x).ok(),
+        _ => None,
+      };
+      let text = index
+        .and_then(|x| strings.get(x as usize))
+        .ok_or_else(|| BinaryError::Cbor("Dangling string reference".into()))?;
+      Value::Text(text.clone())
+    }
+    Value::Array(items) => Value::Array(
+      items
+        .into_iter()
+        .map(|x| resolve(x, strings))
+        .collect::<Result<_, _>>()?,
+    ),
+    Value::Map(entries) => Value::Map(
+      entries
+        .into_iter()
+        .map(|(k, v)| Ok((resolve(k, strings)?, resolve(v, strings)?)))
+        .collect::<Result<_, BinaryError>>()?,
+    ),
+    Value::Tag(tag, inner) => Value::Tag(tag, Box::new(resolve(*inner, strings)?)),
+    other => other,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{history_from_bytes, history_to_bytes, BinaryError};
+  use crate::{utils::time::now, Group, Lesson, Num, Snapshot};
+
+  fn snapshot(names: &[&str]) -> Snapshot {
+    let lessons = names
+      .iter()
+      .enumerate()
+      .map(|(i, name)| Lesson {
+        num: Num::Actual((i + 1).to_string()),
+        name: name.to_string(),
+        teacher: Some("Иванов И.И.".into()),
+        classroom: Some("305".into()),
+        ..Default::default()
+      })
+      .collect();
+    Snapshot::new(vec![Group { uid: "".into(), name: "Ит1-22".into(), lessons }], now())
+  }
+
+  #[test]
+  fn snapshot_round_trips() {
+    let snapshot = snapshot(&["Математика", "Физика"]);
+    let decoded = Snapshot::from_bytes(&snapshot.to_bytes()).unwrap();
+    assert_eq!(decoded.uid, snapshot.uid);
+    assert_eq!(decoded.groups[0].lessons[1].num, Num::Actual("2".into()));
+    assert!(matches!(Snapshot::from_bytes(b"JSON"), Err(BinaryError::Header)));
+  }
+
+  #[test]
+  fn history_stores_strings_once() {
+    let snapshots = (0..20)
+      .map(|_| snapshot(&["Математика", "Физика", "Математика"]))
+      .collect::<Vec<_>>();
+    let bytes = history_to_bytes(&snapshots);
+    let json = serde_json::to_vec(&snapshots).unwrap();
+    assert!(bytes.len() * 2 < json.len(), "{} vs {}", bytes.len(), json.len());
+
+    let decoded = history_from_bytes(&bytes).unwrap();
+    assert_eq!(decoded.len(), 20);
+    assert!(decoded.iter().zip(snapshots.iter()).all(|(a, b)| a.uid == b.uid));
+  }
+}
@@ -1,6 +1,6 @@
 use std::env;
 
-use crate::Snapshot;
+use crate::{legacy::VersionedUid, Snapshot};
 use log::debug;
 
 lazy_static::lazy_static! {
@@ -10,13 +10,20 @@ lazy_static::lazy_static! {
       .collect::<Vec<String>>();
 }
 
+/// Groups from `GROUPS` whose lessons differ between the snapshots.
 pub fn distinct(previous: Option<&Snapshot>, new: Option<&Snapshot>) -> Vec<String> {
+  distinct_with(&GROUPS, previous, new)
+}
+
+/// Same as [`distinct`], but over the given group names instead of `GROUPS`.
+pub fn distinct_with(groups: &[String], previous: Option<&Snapshot>, new: Option<&Snapshot>) -> Vec<String> {
   debug!("Comparing {:?} & {:?}", previous.map(|x| &x.uid), new.map(|x| &x.uid));
   let (previous, new) = match (previous, new) {
-    (Some(l), Some(r)) if l.uid == r.uid => return vec![],
+    (Some(l), Some(r)) if r.matches_uid(&l.uid) => return vec![],
+    (Some(_), Some(r)) if is_suspicious(r) => return vec![],
     (Some(l), Some(r)) => (l, r),
     (Some(_), None) => return vec![],
-    (None, Some(_)) => return GROUPS.clone(),
+    (None, Some(_)) => return groups.to_vec(),
     (None, None) => return vec![],
   };
 
@@ -26,7 +33,7 @@ pub fn distinct(previous: Option<&Snapshot>, new: Option<&Snapshot>) -> Vec<Stri
     new.groups.iter().map(|x| &x.name).collect::<Vec<&String>>()
   );
 
-  let mut changes = GROUPS.clone();
+  let mut changes = groups.to_vec();
 
   let is_updated = |name: &String| -> bool {
     let prev = previous.group(&*name);
@@ -35,7 +42,7 @@ pub fn distinct(previous: Option<&Snapshot>, new: Option<&Snapshot>) -> Vec<Stri
     let result = match (prev, new) {
       (None, Some(_)) => true,
       (Some(_), None) => true,
-      (Some(p), Some(n)) if p.uid != n.uid => true,
+      (Some(p), Some(n)) if !n.matches_uid(&p.uid) => true,
       _ => false,
     };
 
@@ -54,13 +61,47 @@ pub fn distinct(previous: Option<&Snapshot>, new: Option<&Snapshot>) -> Vec<Stri
   changes
 }
 
+pub fn is_personal_updated(previous: Option<&Snapshot>, new: Option<&Snapshot>, group: &str, subgroup: Option<u8>) -> bool {
+  if matches!(new, Some(s) if is_suspicious(s)) {
+    return false;
+  }
+
+  let prev = previous.and_then(|s| s.personal(group, subgroup));
+  let new = new.and_then(|s| s.personal(group, subgroup));
+  debug!("Comparing {} ({:?}) {:?} & {:?}", group, subgroup, prev.as_ref().map(|x| &x.uid), new.as_ref().map(|x| &x.uid));
+
+  match (prev, new) {
+    (Some(p), Some(n)) => p.uid != n.uid,
+    (None, None) => false,
+    _ => true,
+  }
+}
+
+/// An empty snapshot of a regular day is most likely a page the parser failed to read, so it is
+/// not reported as a change. Holidays and other special days are expected to be empty.
+fn is_suspicious(snapshot: &Snapshot) -> bool {
+  let suspicious = snapshot.groups.is_empty() && snapshot.kind.is_regular();
+  if suspicious {
+    debug!("Snapshot {} of a regular day has no groups", snapshot.uid);
+  }
+  suspicious
+}
+
 #[cfg(test)]
 mod tests {
-  use crate::{compare::distinct, utils::time::now, Group, Lesson, Num, Snapshot, Uid};
+  use crate::{
+    compare::{distinct_with, is_personal_updated},
+    utils::time::now,
+    DayKind, Group, Lesson, Num, Snapshot, Uid,
+  };
+
+  fn groups() -> Vec<String> {
+    vec!["Group".into(), "Other".into()]
+  }
 
   #[test]
   fn different_group_lessons() {
-    let lesson = Lesson { num: Num::Actual("1".into()), name: "123".into(), subgroup: None, teacher: None, classroom: None };
+    let lesson = Lesson { num: Num::Actual("1".into()), name: "123".into(), ..Default::default() };
     let mut group = Group::new("Group".into());
     group.lessons = vec![lesson.clone()];
     let mut snapshot_1 = Snapshot::new(vec![group.clone()], now());
@@ -72,6 +113,71 @@ mod tests {
     println!("{:?}", snapshot_1);
     snapshot_1.refresh();
     snapshot_2.refresh();
-    assert_eq!(vec!["Group".to_string()], distinct(Some(&snapshot_1), Some(&snapshot_2)))
+    assert_eq!(vec!["Group".to_string()], distinct_with(&groups(), Some(&snapshot_1), Some(&snapshot_2)))
+  }
+
+  #[test]
+  fn other_subgroup_changes_are_ignored() {
+    let lesson = Lesson { num: Num::Actual("1".into()), name: "123".into(), subgroup: Some(1), ..Default::default() };
+    let mut group = Group::new("Group".into());
+    group.lessons = vec![lesson.clone()];
+    let snapshot_1 = Snapshot::new(vec![group.clone()], now());
+
+    group.lessons = vec![lesson.clone(), Lesson { num: Num::Actual("2".into()), subgroup: Some(2), ..lesson }];
+    let snapshot_2 = Snapshot::new(vec![group], now());
+
+    assert!(!is_personal_updated(Some(&snapshot_1), Some(&snapshot_2), "Group", Some(1)));
+    assert!(is_personal_updated(Some(&snapshot_1), Some(&snapshot_2), "Group", Some(2)));
+    assert!(is_personal_updated(Some(&snapshot_1), Some(&snapshot_2), "Group", None));
+  }
+
+  #[test]
+  fn empty_days_are_changes_only_when_marked() {
+    let lesson = Lesson { num: Num::Actual("1".into()), name: "123".into(), ..Default::default() };
+    let mut group = Group::new("Group".into());
+    group.lessons = vec![lesson];
+    let snapshot = Snapshot::new(vec![group], now());
+    let mut empty = Snapshot::new(vec![], now());
+
+    assert!(distinct_with(&groups(), Some(&snapshot), Some(&empty)).is_empty());
+    assert!(!is_personal_updated(Some(&snapshot), Some(&empty), "Group", None));
+
+    empty.kind = DayKind::Holiday;
+    empty.refresh();
+    assert_eq!(vec!["Group".to_string()], distinct_with(&groups(), Some(&snapshot), Some(&empty)));
+    assert!(is_personal_updated(Some(&snapshot), Some(&empty), "Group", None));
+  }
+
+  #[test]
+  fn reordered_rows_are_not_changes() {
+    let lesson = |num: &str, name: &str| Lesson { num: Num::Actual(num.into()), name: name.into(), ..Default::default() };
+    let group = |name: &str, lessons: Vec<Lesson>| Group { lessons, ..Group::new(name.into()) };
+    let snapshot = Snapshot::new(
+      vec![
+        group("Group", vec![lesson("1", "Математика"), lesson("2", "Физика"), lesson("2", "Химия")]),
+        group("Other", vec![lesson("3", "История")]),
+      ],
+      now(),
+    );
+    let reordered = Snapshot::new(
+      vec![
+        group("Other", vec![lesson("3", "История")]),
+        group("Group", vec![lesson("2", "Химия"), lesson("1", "Математика"), lesson("2", "Физика")]),
+      ],
+      now(),
+    );
+
+    assert_eq!(snapshot.uid, reordered.uid);
+    assert_eq!(snapshot.group("Group").unwrap().uid, reordered.group("Group").unwrap().uid);
+    assert!(distinct_with(&groups(), Some(&snapshot), Some(&reordered)).is_empty());
+
+    let moved = Snapshot::new(
+      vec![
+        group("Group", vec![lesson("1", "Математика"), lesson("2", "Физика")]),
+        group("Other", vec![lesson("3", "История"), lesson("2", "Химия")]),
+      ],
+      now(),
+    );
+    assert_ne!(snapshot.uid, moved.uid);
   }
 }
@@ -4,20 +4,23 @@ use serde::{Deserialize, Serialize};
 use crate::Num;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct DefaultDay {
   pub day: Weekday,
   pub groups: Vec<DefaultGroup>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct DefaultGroup {
   pub name: String,
   pub lessons: Vec<DefaultLesson>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct DefaultLesson {
-  #[serde(skip_serializing_if = "Num::is_none")]
+  #[serde(default, skip_serializing_if = "Num::is_unset")]
   pub num: Num,
   
   pub name: String,
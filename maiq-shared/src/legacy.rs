@@ -0,0 +1,142 @@
+//! Ids as they were computed before fields were hashed canonically. Stored snapshots and clients keep
+//! these ids, so they are still needed to tell whether a stored snapshot is the same as a parsed one.
+
+use sha2::{digest::Digest, Sha256};
+
+use crate::{
+  num_as_bytes,
+  utils::{bytes_as_str, UID_LEN},
+  Group, Lesson, Num, Snapshot, Uid,
+};
+
+pub trait LegacyUid {
+  fn legacy_uid_bytes(&self) -> [u8; 32];
+
+  fn legacy_uid(&self) -> String {
+    bytes_as_str(&self.legacy_uid_bytes())
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UidVersion {
+  /// [`LegacyUid`], 10 characters.
+  Legacy,
+  /// [`Uid`], canonical hashing in base32.
+  V2,
+}
+
+impl UidVersion {
+  /// Told apart by length alone: legacy ids are 10 characters long, current ones [`UID_LEN`]. Anything
+  /// else, shortened ids included, is neither.
+  pub fn of(uid: &str) -> Option<UidVersion> {
+    match uid.len() {
+      10 => Some(UidVersion::Legacy),
+      UID_LEN => Some(UidVersion::V2),
+      _ => None,
+    }
+  }
+}
+
+/// Both id versions side by side, for as long as stored snapshots carry legacy ids.
+pub trait VersionedUid: Uid + LegacyUid {
+  fn uid_v2(&self) -> String {
+    self.uid_with_len(UID_LEN)
+  }
+
+  fn uid_of(&self, version: UidVersion) -> String {
+    match version {
+      UidVersion::Legacy => self.legacy_uid(),
+      UidVersion::V2 => self.uid_v2(),
+    }
+  }
+
+  /// Whether `uid`, of either version, is the id of this content. Ids of any other length never match.
+  fn matches_uid(&self, uid: &str) -> bool {
+    match UidVersion::of(uid) {
+      Some(version) => self.uid_of(version) == uid,
+      None => false,
+    }
+  }
+}
+
+impl<T: Uid + LegacyUid> VersionedUid for T {}
+
+impl LegacyUid for Num {
+  fn legacy_uid_bytes(&self) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    let mut res = [0u8; 32];
+
+    match self {
+      Num::Actual(x) => hasher.update(x),
+      _ => hasher.update([0]),
+    }
+    hasher.finalize_into((&mut res).into());
+    res
+  }
+}
+
+impl LegacyUid for Lesson {
+  fn legacy_uid_bytes(&self) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    let mut res = [0u8; 32];
+    hasher.update(self.classroom.clone().unwrap_or_default().as_bytes());
+    hasher.update(self.teacher.clone().unwrap_or_default().as_bytes());
+    hasher.update(self.name.as_bytes());
+    hasher.update(num_as_bytes!(self.subgroup.unwrap_or(0), u8));
+    hasher.update(self.num.legacy_uid_bytes());
+    hasher.finalize_into((&mut res).into());
+    res
+  }
+}
+
+impl LegacyUid for Group {
+  fn legacy_uid_bytes(&self) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    let mut res = [0u8; 32];
+    hasher.update(&self.name);
+    self.lessons.iter().for_each(|l| hasher.update(l.legacy_uid_bytes()));
+    hasher.finalize_into((&mut res).into());
+    res
+  }
+}
+
+impl LegacyUid for Snapshot {
+  fn legacy_uid_bytes(&self) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    let mut res = [0u8; 32];
+    self.groups.iter().for_each(|g| hasher.update(g.legacy_uid_bytes()));
+    if !self.kind.is_regular() {
+      hasher.update(self.kind.to_string().as_bytes());
+    }
+    hasher.finalize_into((&mut res).into());
+    res
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{LegacyUid, UidVersion, VersionedUid};
+  use crate::{compare::distinct_with, utils::time::now, Group, Lesson, Num, Snapshot, Uid};
+
+  #[test]
+  fn stored_legacy_uids_still_match() {
+    let lesson = Lesson { num: Num::Actual("1".into()), name: "Математика".into(), ..Default::default() };
+    let snapshot = Snapshot::new(vec![Group { lessons: vec![lesson], ..Group::new("Group".into()) }], now());
+    assert_eq!(snapshot.uid, snapshot.uid_v2());
+    assert_eq!(UidVersion::of(&snapshot.legacy_uid()), Some(UidVersion::Legacy));
+    assert_eq!(UidVersion::of(&snapshot.uid), Some(UidVersion::V2));
+    assert!(snapshot.matches_uid(&snapshot.uid));
+    assert!(snapshot.matches_uid(&snapshot.legacy_uid()));
+    assert!(!snapshot.matches_uid("0123456789"));
+
+    for uid in ["", &snapshot.uid[..1], &snapshot.uid[..12], &snapshot.uid_with_len(10)] {
+      assert!(!snapshot.matches_uid(uid), "{:?} matched", uid);
+    }
+    assert_eq!(UidVersion::of(&snapshot.uid[..12]), None);
+
+    let mut stored = snapshot.clone();
+    stored.uid = snapshot.legacy_uid();
+    stored.groups[0].uid = snapshot.groups[0].legacy_uid();
+    assert!(distinct_with(&["Group".into()], Some(&stored), Some(&snapshot)).is_empty());
+  }
+}
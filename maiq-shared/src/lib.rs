@@ -1,21 +1,37 @@
+pub mod bells;
+#[cfg(feature = "binary")]
+pub mod binary;
 pub mod compare;
 pub mod default;
+pub mod legacy;
+pub mod lookup;
+pub mod merge;
+pub mod schema;
+pub mod timeline;
 pub mod utils;
 
 use std::fmt::Display;
 
 use chrono::{DateTime, Datelike, Duration, Utc};
+use lookup::Lookup;
 use serde::{Deserialize, Serialize};
-use sha2::{digest::Digest, Sha256};
-use utils::{bytes_as_str, time};
+use utils::{encode_uid, time, FieldHasher, UID_LEN};
 
 pub trait Uid {
   fn refresh(&mut self);
   fn uid_bytes(&self) -> [u8; 32];
+
+  /// Id of `len` characters, up to [`utils::MAX_UID_LEN`]. `uid` fields are [`UID_LEN`] long.
+  fn uid_with_len(&self, len: usize) -> String {
+    encode_uid(&self.uid_bytes(), len)
+  }
 }
 
+/// Serialized as a string for [`Num::Actual`] and `{"previous": true}` for [`Num::Previous`]. [`Num::None`]
+/// is `null` on its own, but lesson fields skip it with [`Num::is_unset`] and read a missing field back as
+/// [`Num::None`], so every variant survives a round trip.
 #[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize, Clone)]
-#[serde(untagged)]
+#[serde(from = "NumRepr", into = "NumRepr")]
 pub enum Num {
   Actual(String),
   Previous,
@@ -27,6 +43,50 @@ impl Num {
   pub fn is_none(&self) -> bool {
     !matches!(self, Num::Actual(_))
   }
+
+  pub fn is_unset(&self) -> bool {
+    matches!(self, Num::None)
+  }
+}
+
+#[derive(Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+enum NumRepr {
+  Actual(String),
+  Previous { previous: bool },
+  None,
+}
+
+impl From<NumRepr> for Num {
+  fn from(value: NumRepr) -> Self {
+    match value {
+      NumRepr::Actual(x) => Num::Actual(x),
+      NumRepr::Previous { .. } => Num::Previous,
+      NumRepr::None => Num::None,
+    }
+  }
+}
+
+impl From<Num> for NumRepr {
+  fn from(value: Num) -> Self {
+    match value {
+      Num::Actual(x) => NumRepr::Actual(x),
+      Num::Previous => NumRepr::Previous { previous: true },
+      Num::None => NumRepr::None,
+    }
+  }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for Num {
+  fn schema_name() -> String {
+    "Num".into()
+  }
+
+  fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+    NumRepr::json_schema(gen)
+  }
 }
 
 impl Display for Num {
@@ -41,21 +101,20 @@ impl Display for Num {
 
 impl Uid for Num {
   fn uid_bytes(&self) -> [u8; 32] {
-    let mut hasher = Sha256::new();
-    let mut res = [0u8; 32];
-
+    let mut hasher = FieldHasher::new("num");
     match self {
-      Num::Actual(x) => hasher.update(&x),
-      _ => hasher.update([0]),
-    }
-    hasher.finalize_into((&mut res).into());
-    res
+      Num::Actual(x) => hasher.field(&[1]).str(x),
+      Num::Previous => hasher.field(&[2]),
+      Num::None => hasher.field(&[0]),
+    };
+    hasher.finish()
   }
 
   fn refresh(&mut self) {}
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Group {
   pub uid: String,
   pub name: String,
@@ -66,26 +125,44 @@ impl Group {
   pub fn new(name: String) -> Self {
     Self { uid: "EMPTY".into(), name, lessons: vec![] }
   }
+
+  pub fn personal(&self, subgroup: Option<u8>) -> Group {
+    let mut group = Group {
+      uid: self.uid.clone(),
+      name: self.name.clone(),
+      lessons: self.lessons.iter().filter(|l| l.is_for(subgroup)).cloned().collect(),
+    };
+    group.refresh();
+    group
+  }
 }
 
 impl Uid for Group {
+  /// Lessons are hashed in a canonical order, rows moved around on the page keep the uid.
   fn uid_bytes(&self) -> [u8; 32] {
-    let mut hasher = Sha256::new();
-    let mut res = [0u8; 32];
-    hasher.update(&self.name);
-    self.lessons.iter().for_each(|l| hasher.update(l.uid_bytes()));
-    hasher.finalize_into((&mut res).into());
-    res
+    let mut hasher = FieldHasher::new("group");
+    hasher.str(&self.name);
+    sorted_uids(&self.lessons).iter().for_each(|l| {
+      hasher.field(l);
+    });
+    hasher.finish()
   }
 
   fn refresh(&mut self) {
-    self.uid = bytes_as_str(&self.uid_bytes());
+    self.uid = self.uid_with_len(UID_LEN);
   }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+fn sorted_uids<T: Uid>(items: &[T]) -> Vec<[u8; 32]> {
+  let mut uids = items.iter().map(|x| x.uid_bytes()).collect::<Vec<_>>();
+  uids.sort_unstable();
+  uids
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Lesson {
-  #[serde(skip_serializing_if = "Num::is_none")]
+  #[serde(skip_serializing_if = "Num::is_unset")]
   #[serde(default)]
   pub num: Num,
   pub name: String,
@@ -95,36 +172,152 @@ pub struct Lesson {
   pub teacher: Option<String>,
   #[serde(skip_serializing_if = "Option::is_none")]
   pub classroom: Option<String>,
+  #[serde(default, skip_serializing_if = "LessonKind::is_regular")]
+  pub kind: LessonKind,
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub is_remote: bool,
+  /// Name without the kind and format markers, if there were any.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub clean_name: Option<String>,
+  #[serde(default, skip_serializing_if = "LessonStatus::is_scheduled")]
+  pub status: LessonStatus,
+  /// Lesson of the default timetable this one takes the place of, known for cancellations.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub replaces: Option<Box<Lesson>>,
+  #[serde(default, skip_serializing_if = "LessonSource::is_page")]
+  pub source: LessonSource,
+  /// Cells of the page row the lesson was parsed from.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub raw: Vec<String>,
+}
+
+impl Lesson {
+  pub fn display_name(&self) -> &str {
+    self.clean_name.as_deref().unwrap_or(&self.name)
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.status == LessonStatus::Cancelled
+  }
+
+  pub fn is_for(&self, subgroup: Option<u8>) -> bool {
+    match (self.subgroup, subgroup) {
+      (Some(own), Some(sub)) => own == sub,
+      _ => true,
+    }
+  }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum LessonKind {
+  #[default]
+  Regular,
+  Lecture,
+  Practice,
+  Lab,
+  Exam,
+  Consultation,
+}
+
+impl LessonKind {
+  pub fn is_regular(&self) -> bool {
+    *self == LessonKind::Regular
+  }
+}
+
+impl Display for LessonKind {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(match self {
+      LessonKind::Regular => "Занятие",
+      LessonKind::Lecture => "Лекция",
+      LessonKind::Practice => "Практика",
+      LessonKind::Lab => "Лабораторная",
+      LessonKind::Exam => "Экзамен",
+      LessonKind::Consultation => "Консультация",
+    })
+  }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum LessonStatus {
+  #[default]
+  Scheduled,
+  Cancelled,
+}
+
+impl LessonStatus {
+  pub fn is_scheduled(&self) -> bool {
+    *self == LessonStatus::Scheduled
+  }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum LessonSource {
+  #[default]
+  Page,
+  Default,
+  DefaultWithPageClassroom,
+}
+
+impl LessonSource {
+  pub fn is_page(&self) -> bool {
+    *self == LessonSource::Page
+  }
 }
 
 impl Uid for Lesson {
   fn uid_bytes(&self) -> [u8; 32] {
-    let mut hasher = Sha256::new();
-    let mut res = [0u8; 32];
-    hasher.update(self.classroom.clone().unwrap_or_default().as_bytes());
-    hasher.update(self.teacher.clone().unwrap_or_default().as_bytes());
-    hasher.update(self.name.as_bytes());
-    hasher.update(&num_as_bytes!(self.subgroup.unwrap_or(0), u8));
-    hasher.update(self.num.uid_bytes());
-    hasher.finalize_into((&mut res).into());
-    res
+    FieldHasher::new("lesson")
+      .field(&self.num.uid_bytes())
+      .field(&self.subgroup.map_or(vec![], |x| vec![x]))
+      .str(&self.name)
+      .opt_str(self.teacher.as_deref())
+      .opt_str(self.classroom.as_deref())
+      .field(&[self.status as u8])
+      .finish()
   }
 
   fn refresh(&mut self) {}
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Snapshot {
+  #[serde(default = "schema::legacy_version")]
+  pub schema_version: u32,
   pub date: DateTime<Utc>,
   pub parsed_date: DateTime<Utc>,
   pub uid: String,
   pub groups: Vec<Group>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub discovered_groups: Vec<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub encoding: Option<String>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub notes: Vec<Notice>,
+  #[serde(default, skip_serializing_if = "DayKind::is_regular")]
+  pub kind: DayKind,
 }
 
 impl Snapshot {
   pub fn new(groups: Vec<Group>, date: DateTime<Utc>) -> Self {
-    let now = chrono::Utc::now() + Duration::hours(3);
-    let mut snapshot = Self { date, uid: "".into(), groups, parsed_date: now };
+    let mut snapshot = Self {
+      schema_version: schema::SCHEMA_VERSION,
+      date,
+      uid: "".into(),
+      groups,
+      parsed_date: time::now(),
+      discovered_groups: vec![],
+      encoding: None,
+      notes: vec![],
+      kind: DayKind::Regular,
+    };
     snapshot.refresh();
     snapshot
   }
@@ -137,6 +330,10 @@ impl Snapshot {
     self.group(name).cloned()
   }
 
+  pub fn lookup_group(&self, query: &str) -> Lookup<&Group> {
+    lookup::lookup(query, &self.groups, |g| g.name.as_str())
+  }
+
   pub fn age(&self) -> Duration {
     time::now() - self.parsed_date
   }
@@ -145,41 +342,153 @@ impl Snapshot {
     self.date.iso_week().week() % 2 != 0
   }
 
+  pub fn personal(&self, name: &str, subgroup: Option<u8>) -> Option<Group> {
+    self.group(name).map(|g| g.personal(subgroup))
+  }
+
   pub fn tiny(&self, group: &str) -> TinySnapshot {
-    let group = self
-      .groups
-      .iter()
-      .find(|g| g.name == group)
-      .and_then(|g| Some(g.to_owned()));
+    self.tiny_personal(group, None)
+  }
+
+  pub fn notes_for(&self, group: &str) -> Vec<&Notice> {
+    self.notes.iter().filter(|n| n.is_for(group)).collect()
+  }
 
-    TinySnapshot { uid: self.uid.clone(), date: self.date, parsed_date: self.parsed_date, group }
+  pub fn tiny_personal(&self, group: &str, subgroup: Option<u8>) -> TinySnapshot {
+    let notes = self.notes_for(group).into_iter().cloned().collect();
+    let group = self.personal(group, subgroup);
+    TinySnapshot {
+      schema_version: schema::SCHEMA_VERSION,
+      uid: self.uid.clone(),
+      date: self.date,
+      parsed_date: self.parsed_date,
+      kind: self.kind,
+      subgroup,
+      group,
+      notes,
+    }
   }
 }
 
 impl Uid for Snapshot {
+  /// Groups are hashed in a canonical order, like lessons of a group. Notes keep the page order.
   fn uid_bytes(&self) -> [u8; 32] {
-    let mut hasher = Sha256::new();
-    let mut res = [0u8; 32];
-    self.groups.iter().for_each(|g| hasher.update(&g.uid_bytes()));
-    hasher.finalize_into((&mut res).into());
-    res
+    let mut hasher = FieldHasher::new("snapshot");
+    hasher.field(&(self.groups.len() as u64).to_le_bytes());
+    sorted_uids(&self.groups).iter().for_each(|g| {
+      hasher.field(g);
+    });
+    hasher.field(&[self.kind as u8]);
+    hasher.field(&(self.notes.len() as u64).to_le_bytes());
+    self.notes.iter().for_each(|n| {
+      hasher.field(&n.uid_bytes());
+    });
+    let mut discovered = self.discovered_groups.iter().collect::<Vec<&String>>();
+    discovered.sort();
+    discovered.iter().for_each(|name| {
+      hasher.str(name);
+    });
+    hasher.finish()
   }
 
   fn refresh(&mut self) {
     self.groups.iter_mut().for_each(|g| g.refresh());
-    self.uid = bytes_as_str(&self.uid_bytes());
+    self.uid = self.uid_with_len(UID_LEN);
+  }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum DayKind {
+  #[default]
+  Regular,
+  Shortened,
+  Holiday,
+  Practice,
+  SelfStudy,
+}
+
+impl DayKind {
+  pub fn is_regular(&self) -> bool {
+    *self == DayKind::Regular
+  }
+}
+
+impl Display for DayKind {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(match self {
+      DayKind::Regular => "Обычный день",
+      DayKind::Shortened => "Сокращённый день",
+      DayKind::Holiday => "Выходной",
+      DayKind::Practice => "Практика",
+      DayKind::SelfStudy => "День самоподготовки",
+    })
   }
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct TinySnapshot {
+  #[serde(default = "schema::legacy_version")]
+  pub schema_version: u32,
   pub uid: String,
   pub date: DateTime<Utc>,
   pub parsed_date: DateTime<Utc>,
+  #[serde(default, skip_serializing_if = "DayKind::is_regular")]
+  pub kind: DayKind,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub subgroup: Option<u8>,
   pub group: Option<Group>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub notes: Vec<Notice>,
+}
+
+/// `uid` is the id of the whole snapshot, [`Uid::uid_bytes`] covers only what the group sees.
+impl Uid for TinySnapshot {
+  fn uid_bytes(&self) -> [u8; 32] {
+    let mut hasher = FieldHasher::new("tiny");
+    hasher.field(&self.group.as_ref().map_or(vec![], |g| g.uid_bytes().to_vec()));
+    hasher.field(&self.subgroup.map_or(vec![], |x| vec![x]));
+    hasher.field(&[self.kind as u8]);
+    self.notes.iter().for_each(|n| {
+      hasher.field(&n.uid_bytes());
+    });
+    hasher.finish()
+  }
+
+  fn refresh(&mut self) {}
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Notice {
+  pub text: String,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub groups: Vec<String>,
+}
+
+impl Notice {
+  pub fn is_for(&self, group: &str) -> bool {
+    self.groups.is_empty() || self.groups.iter().any(|g| g == group)
+  }
+}
+
+impl Uid for Notice {
+  fn uid_bytes(&self) -> [u8; 32] {
+    let mut hasher = FieldHasher::new("notice");
+    hasher.str(&self.text);
+    self.groups.iter().for_each(|g| {
+      hasher.str(g);
+    });
+    hasher.finish()
+  }
+
+  fn refresh(&mut self) {}
 }
 
 #[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum Fetch {
   Today,
   Next,
@@ -0,0 +1,140 @@
+const HOMOGLYPHS: [(char, char); 12] = [
+  ('a', 'а'),
+  ('b', 'в'),
+  ('c', 'с'),
+  ('e', 'е'),
+  ('h', 'н'),
+  ('k', 'к'),
+  ('m', 'м'),
+  ('o', 'о'),
+  ('p', 'р'),
+  ('t', 'т'),
+  ('x', 'х'),
+  ('y', 'у'),
+];
+
+const DASHES: [char; 6] = ['‐', '‑', '‒', '–', '—', '−'];
+
+const MAX_SUGGESTIONS: usize = 5;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Lookup<T> {
+  Found(T),
+  Missing(Vec<String>),
+}
+
+impl<T> Lookup<T> {
+  pub fn found(self) -> Option<T> {
+    match self {
+      Lookup::Found(x) => Some(x),
+      Lookup::Missing(_) => None,
+    }
+  }
+}
+
+pub fn normalize(name: &str) -> String {
+  name
+    .chars()
+    .filter(|c| !c.is_whitespace())
+    .flat_map(char::to_lowercase)
+    .map(|c| match c {
+      c if DASHES.contains(&c) || c == '_' => '-',
+      c => HOMOGLYPHS
+        .iter()
+        .find(|(latin, _)| *latin == c)
+        .map(|(_, cyr)| *cyr)
+        .unwrap_or(c),
+    })
+    .collect()
+}
+
+pub fn lookup<T, F>(query: &str, items: impl IntoIterator<Item = T>, name: F) -> Lookup<T>
+where
+  F: Fn(&T) -> &str,
+{
+  let query = normalize(query);
+  let mut ranked = vec![];
+
+  for item in items {
+    let normalized = normalize(name(&item));
+    if normalized == query {
+      return Lookup::Found(item);
+    }
+
+    let distance = distance(&query, &normalized);
+    if distance <= threshold(&query) {
+      ranked.push((distance, name(&item).to_string()));
+    }
+  }
+
+  ranked.sort();
+  Lookup::Missing(
+    ranked
+      .into_iter()
+      .take(MAX_SUGGESTIONS)
+      .map(|(_, name)| name)
+      .collect(),
+  )
+}
+
+fn threshold(query: &str) -> usize {
+  (query.chars().count() / 3).max(2)
+}
+
+fn distance(a: &str, b: &str) -> usize {
+  let b = b.chars().collect::<Vec<char>>();
+  let mut row = (0..=b.len()).collect::<Vec<usize>>();
+
+  for (i, ca) in a.chars().enumerate() {
+    let mut diagonal = row[0];
+    row[0] = i + 1;
+    for (j, cb) in b.iter().enumerate() {
+      let above = row[j + 1];
+      row[j + 1] = match ca == *cb {
+        true => diagonal,
+        false => 1 + diagonal.min(above).min(row[j]),
+      };
+      diagonal = above;
+    }
+  }
+
+  row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{distance, lookup, normalize, Lookup};
+
+  const NAMES: [&str; 4] = ["Ир1-21", "Ир3-21", "Ип1-20", "Са1-21"];
+
+  fn find(query: &str) -> Lookup<&'static str> {
+    lookup(query, NAMES, |x| x)
+  }
+
+  #[test]
+  fn normalizes_case_spaces_dashes_and_homoglyphs() {
+    assert_eq!(normalize("ИР 1–21"), "ир1-21");
+    assert_eq!(normalize("Иp1-21"), "ир1-21");
+    assert_eq!(normalize(" CA1 - 21 "), "са1-21");
+  }
+
+  #[test]
+  fn finds_by_normalized_name() {
+    assert_eq!(find("ир1-21"), Lookup::Found("Ир1-21"));
+    assert_eq!(find("ИР 1-21"), Lookup::Found("Ир1-21"));
+    assert_eq!(find("Иp1-21"), Lookup::Found("Ир1-21"));
+  }
+
+  #[test]
+  fn suggests_closest_on_miss() {
+    assert_eq!(find("Ир2-21"), Lookup::Missing(vec!["Ир1-21".into(), "Ир3-21".into()]));
+    assert_eq!(find("Кс5-20"), Lookup::Missing(vec![]));
+  }
+
+  #[test]
+  fn levenshtein_distance() {
+    assert_eq!(distance("", "abc"), 3);
+    assert_eq!(distance("kitten", "sitting"), 3);
+    assert_eq!(distance("ир1-21", "ир1-21"), 0);
+  }
+}
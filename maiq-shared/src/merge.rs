@@ -0,0 +1,178 @@
+//! Reconciling snapshots of the same day. The next day page published in advance and the today page
+//! of that day describe the same date, so the newer one wins, its group set included.
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{legacy::VersionedUid, Snapshot, Uid};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+  /// Both snapshots list the group with different lessons, the newer version is kept.
+  Changed { older: String, newer: String },
+  /// Only the older snapshot lists the group. It is dropped, the newer page may have removed it on purpose
+  /// (a holiday, the group's lessons moved), the conflict only lets the caller know.
+  MissingInNewer,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Conflict {
+  pub group: String,
+  pub kind: ConflictKind,
+  pub older_parsed: DateTime<Utc>,
+  pub newer_parsed: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Merged {
+  pub snapshot: Snapshot,
+  pub conflicts: Vec<Conflict>,
+}
+
+#[derive(Debug)]
+pub enum MergeError {
+  DifferentDates(NaiveDate, NaiveDate),
+}
+
+impl Display for MergeError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      MergeError::DifferentDates(a, b) => write!(f, "Snapshots of {} and {} can't be merged", a, b),
+    }
+  }
+}
+
+impl std::error::Error for MergeError {}
+
+/// Merges two snapshots of the same date. The snapshot with the later `parsed_date` is taken as is, on a tie
+/// `b` is the newer. Groups that differ or only the older one lists are reported as conflicts.
+pub fn merge(a: &Snapshot, b: &Snapshot) -> Result<Merged, MergeError> {
+  if a.date.date_naive() != b.date.date_naive() {
+    return Err(MergeError::DifferentDates(a.date.date_naive(), b.date.date_naive()));
+  }
+
+  let (older, newer) = match a.parsed_date > b.parsed_date {
+    true => (b, a),
+    false => (a, b),
+  };
+
+  let conflict = |group: &str, kind: ConflictKind| Conflict {
+    group: group.into(),
+    kind,
+    older_parsed: older.parsed_date,
+    newer_parsed: newer.parsed_date,
+  };
+
+  let mut conflicts = vec![];
+  for group in older.groups.iter() {
+    match newer.group(&group.name) {
+      Some(x) if x.uid_bytes() == group.uid_bytes() => (),
+      Some(x) => conflicts.push(conflict(&group.name, ConflictKind::Changed { older: group.uid_v2(), newer: x.uid_v2() })),
+      None => conflicts.push(conflict(&group.name, ConflictKind::MissingInNewer)),
+    }
+  }
+
+  Ok(Merged { snapshot: newer.clone(), conflicts })
+}
+
+/// Authoritative snapshot for every calendar date found in `snapshots`, merged in `parsed_date` order.
+pub fn resolve<'a>(snapshots: impl IntoIterator<Item = &'a Snapshot>) -> BTreeMap<NaiveDate, Merged> {
+  let mut by_date: BTreeMap<NaiveDate, Vec<&Snapshot>> = BTreeMap::new();
+  snapshots
+    .into_iter()
+    .for_each(|s| by_date.entry(s.date.date_naive()).or_default().push(s));
+
+  by_date
+    .into_iter()
+    .map(|(date, mut versions)| {
+      versions.sort_by_key(|s| s.parsed_date);
+      let first = Merged { snapshot: versions[0].clone(), conflicts: vec![] };
+      let merged = versions[1..].iter().fold(first, |acc, next| {
+        let mut merged = merge(&acc.snapshot, next).expect("Versions are grouped by date");
+        merged.conflicts.splice(0..0, acc.conflicts);
+        merged
+      });
+      (date, merged)
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::{Duration, TimeZone, Utc};
+
+  use super::{merge, resolve, ConflictKind};
+  use crate::{DayKind, Group, Lesson, Num, Snapshot, Uid};
+
+  fn snapshot(day: u32, hour: i64, groups: &[(&str, &str)]) -> Snapshot {
+    let date = Utc.with_ymd_and_hms(2023, 9, day, 0, 0, 0).unwrap();
+    let groups = groups
+      .iter()
+      .map(|(name, lesson)| Group {
+        lessons: vec![Lesson { num: Num::Actual("1".into()), name: lesson.to_string(), ..Default::default() }],
+        ..Group::new(name.to_string())
+      })
+      .collect();
+    let mut snapshot = Snapshot::new(groups, date);
+    snapshot.parsed_date = date + Duration::hours(hour);
+    snapshot
+  }
+
+  #[test]
+  fn newer_groups_win() {
+    let friday = snapshot(11, -60, &[("Ит1-22", "Математика"), ("Ир1-22", "Физика"), ("Са1-22", "Химия")]);
+    let monday = snapshot(11, 8, &[("Ир1-22", "История"), ("Ит1-22", "Математика")]);
+
+    for merged in [merge(&friday, &monday).unwrap(), merge(&monday, &friday).unwrap()] {
+      assert_eq!(merged.snapshot.parsed_date, monday.parsed_date);
+      assert_eq!(merged.snapshot.group("Ир1-22").unwrap().lessons[0].name, "История");
+      assert!(merged.snapshot.group("Са1-22").is_none());
+      assert_eq!(merged.snapshot.uid, monday.uid);
+      assert_eq!(merged.conflicts.len(), 2);
+      assert!(matches!(merged.conflicts[0].kind, ConflictKind::Changed { .. }));
+      assert_eq!(merged.conflicts[1].kind, ConflictKind::MissingInNewer);
+    }
+
+    assert!(merge(&friday, &snapshot(12, 8, &[])).is_err());
+  }
+
+  #[test]
+  fn holidays_drop_every_group() {
+    let friday = snapshot(11, -60, &[("Ит1-22", "Математика"), ("Ир1-22", "Физика")]);
+    let mut monday = snapshot(11, 8, &[]);
+    monday.kind = DayKind::Holiday;
+    monday.refresh();
+
+    let merged = merge(&friday, &monday).unwrap();
+    assert_eq!(merged.snapshot.kind, DayKind::Holiday);
+    assert!(merged.snapshot.groups.is_empty());
+    assert_eq!(merged.conflicts.len(), 2);
+    assert!(merged
+      .conflicts
+      .iter()
+      .all(|c| c.kind == ConflictKind::MissingInNewer));
+  }
+
+  #[test]
+  fn resolves_one_snapshot_per_date() {
+    let snapshots = [
+      snapshot(12, 9, &[("Ит1-22", "Физика")]),
+      snapshot(11, 8, &[("Ит1-22", "История")]),
+      snapshot(11, -60, &[("Ит1-22", "Математика")]),
+      snapshot(11, 10, &[("Ит1-22", "Химия")]),
+    ];
+    let resolved = resolve(&snapshots);
+
+    assert_eq!(resolved.len(), 2);
+    let monday = resolved.values().next().unwrap();
+    assert_eq!(monday.snapshot.group("Ит1-22").unwrap().lessons[0].name, "Химия");
+    assert_eq!(monday.conflicts.len(), 2);
+    assert!(resolved.values().nth(1).unwrap().conflicts.is_empty());
+  }
+}
@@ -0,0 +1,160 @@
+use std::{fmt::Display, io::Read};
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// Version of the serialized [`Snapshot`](crate::Snapshot) and [`TinySnapshot`](crate::TinySnapshot).
+/// Dumps written before the field existed are version 1.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// `MIGRATIONS[i]` turns version `i + 1` into version `i + 2`.
+const MIGRATIONS: [fn(&mut Value); 1] = [v1_to_v2];
+
+#[derive(Debug)]
+pub enum SchemaError {
+  Unsupported(u32),
+  Json(serde_json::Error),
+}
+
+impl Display for SchemaError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      SchemaError::Unsupported(v) => write!(f, "Schema version {} is newer than supported {}", v, SCHEMA_VERSION),
+      SchemaError::Json(e) => e.fmt(f),
+    }
+  }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl From<serde_json::Error> for SchemaError {
+  fn from(value: serde_json::Error) -> Self {
+    SchemaError::Json(value)
+  }
+}
+
+pub(crate) fn legacy_version() -> u32 {
+  1
+}
+
+/// Brings a serialized snapshot of any known version up to [`SCHEMA_VERSION`].
+pub fn migrate(mut value: Value) -> Result<Value, SchemaError> {
+  let version = value
+    .get("schema_version")
+    .and_then(|v| v.as_u64())
+    .map(|v| v as u32)
+    .unwrap_or_else(legacy_version);
+
+  if version > SCHEMA_VERSION {
+    return Err(SchemaError::Unsupported(version));
+  }
+
+  for migration in MIGRATIONS.iter().skip(version.saturating_sub(1) as usize) {
+    migration(&mut value);
+  }
+
+  if let Some(object) = value.as_object_mut() {
+    object.insert("schema_version".into(), SCHEMA_VERSION.into());
+  }
+  Ok(value)
+}
+
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, SchemaError> {
+  Ok(serde_json::from_value(migrate(value)?)?)
+}
+
+pub fn from_str<T: DeserializeOwned>(raw: &str) -> Result<T, SchemaError> {
+  from_value(serde_json::from_str(raw)?)
+}
+
+pub fn from_reader<R: Read, T: DeserializeOwned>(reader: R) -> Result<T, SchemaError> {
+  from_value(serde_json::from_reader(reader)?)
+}
+
+#[cfg(feature = "schema")]
+pub fn snapshot_schema() -> schemars::schema::RootSchema {
+  schemars::schema_for!(crate::Snapshot)
+}
+
+#[cfg(feature = "schema")]
+pub fn tiny_snapshot_schema() -> schemars::schema::RootSchema {
+  schemars::schema_for!(crate::TinySnapshot)
+}
+
+/// Version 1 had an untagged `num`, so `null` was written for both a missing and a "previous" num.
+/// Parsed snapshots never kept "previous", so it is read as missing.
+fn v1_to_v2(value: &mut Value) {
+  let groups = match value.get_mut("groups") {
+    Some(Value::Array(groups)) => groups.iter_mut().collect::<Vec<&mut Value>>(),
+    _ => value.get_mut("group").into_iter().collect(),
+  };
+
+  for lessons in groups
+    .into_iter()
+    .filter_map(|g| g.get_mut("lessons"))
+    .filter_map(|l| l.as_array_mut())
+  {
+    for lesson in lessons.iter_mut().filter_map(|l| l.as_object_mut()) {
+      if matches!(lesson.get("num"), Some(Value::Null)) {
+        lesson.remove("num");
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{default::DefaultLesson, schema, Lesson, Num, Snapshot};
+
+  #[test]
+  fn num_round_trips() {
+    for num in [Num::Actual("1".into()), Num::Previous, Num::None] {
+      let lesson = Lesson { num: num.clone(), name: "Математика".into(), ..Default::default() };
+      let json = serde_json::to_string(&lesson).unwrap();
+      assert_eq!(serde_json::from_str::<Lesson>(&json).unwrap().num, num);
+
+      let default = DefaultLesson {
+        num: num.clone(),
+        name: "Математика".into(),
+        is_even: None,
+        subgroup: None,
+        teacher: None,
+        classroom: None,
+      };
+      let json = serde_json::to_string(&default).unwrap();
+      assert_eq!(serde_json::from_str::<DefaultLesson>(&json).unwrap().num, num);
+    }
+
+    assert_eq!(serde_json::to_string(&Num::None).unwrap(), "null");
+    assert_eq!(serde_json::to_string(&Num::Previous).unwrap(), r#"{"previous":true}"#);
+    let lesson = Lesson { name: "Математика".into(), ..Default::default() };
+    assert!(!serde_json::to_string(&lesson).unwrap().contains("\"num\""));
+  }
+
+  #[test]
+  fn migrates_legacy_dumps() {
+    let raw = r#"{"date":"2023-09-11T00:00:00Z","parsed_date":"2023-09-11T08:00:00Z","uid":"abc",
+      "groups":[{"uid":"def","name":"Ит1-22","lessons":[{"num":null,"name":"Математика"}]}]}"#;
+    let snapshot: Snapshot = schema::from_str(raw).unwrap();
+    assert_eq!(snapshot.schema_version, schema::SCHEMA_VERSION);
+    assert_eq!(snapshot.groups[0].lessons[0].num, Num::None);
+
+    let future = raw.replacen('{', r#"{"schema_version":99,"#, 1);
+    assert!(schema::from_str::<Snapshot>(&future).is_err());
+  }
+
+  #[cfg(feature = "schema")]
+  #[test]
+  fn published_schema_is_up_to_date() {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("schema");
+    let bless = std::env::var("BLESS").map(|x| x == "1").unwrap_or(false);
+    for (name, schema) in [("snapshot.json", schema::snapshot_schema()), ("tiny-snapshot.json", schema::tiny_snapshot_schema())] {
+      let actual = serde_json::to_string_pretty(&schema).unwrap() + "\n";
+      let path = dir.join(name);
+      match bless {
+        true => std::fs::write(&path, actual).unwrap(),
+        false => assert_eq!(std::fs::read_to_string(&path).unwrap(), actual, "{} is outdated, run with BLESS=1", name),
+      }
+    }
+  }
+}
@@ -0,0 +1,145 @@
+//! How the schedule of one group evolved during a day, version by version.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{legacy::VersionedUid, Lesson, Snapshot, Uid};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum LessonChange {
+  Added(Lesson),
+  Removed(Lesson),
+  /// A lesson with the same num and subgroup was replaced by another one.
+  Changed {
+    before: Lesson,
+    after: Lesson,
+  },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Version {
+  /// Uid of the group, `None` if this version of the page doesn't list it.
+  pub uid: Option<String>,
+  pub snapshot_uid: String,
+  pub parsed_date: DateTime<Utc>,
+  /// Changes against the previous version, the first one is compared to an empty group.
+  pub changes: Vec<LessonChange>,
+}
+
+/// Versions of `group` on `date` in `parsed_date` order. Snapshots that changed only other groups don't
+/// make a new version. Group uids are recomputed, stored snapshots may carry legacy ones.
+pub fn timeline<'a>(snapshots: impl IntoIterator<Item = &'a Snapshot>, date: NaiveDate, group: &str) -> Vec<Version> {
+  let mut snapshots = snapshots
+    .into_iter()
+    .filter(|s| s.date.date_naive() == date)
+    .collect::<Vec<&Snapshot>>();
+  snapshots.sort_by_key(|s| s.parsed_date);
+
+  let mut versions: Vec<Version> = vec![];
+  let mut previous: &[Lesson] = &[];
+  for snapshot in snapshots {
+    let current = snapshot.group(group);
+    let uid = current.map(|g| g.uid_v2());
+    match versions.last() {
+      Some(last) if last.uid == uid => continue,
+      None if uid.is_none() => continue,
+      _ => (),
+    }
+
+    let lessons = current.map(|g| g.lessons.as_slice()).unwrap_or_default();
+    versions.push(Version {
+      uid,
+      snapshot_uid: snapshot.uid.clone(),
+      parsed_date: snapshot.parsed_date,
+      changes: diff(previous, lessons),
+    });
+    previous = lessons;
+  }
+  versions
+}
+
+pub fn diff(before: &[Lesson], after: &[Lesson]) -> Vec<LessonChange> {
+  let mut removed = without(before, after);
+  let mut changes = vec![];
+
+  for lesson in without(after, before) {
+    match removed
+      .iter()
+      .position(|x| x.num == lesson.num && x.subgroup == lesson.subgroup)
+    {
+      Some(i) => changes.push(LessonChange::Changed { before: removed.remove(i).clone(), after: lesson.clone() }),
+      None => changes.push(LessonChange::Added(lesson.clone())),
+    }
+  }
+
+  changes.extend(removed.into_iter().map(|x| LessonChange::Removed(x.clone())));
+  changes
+}
+
+/// Lessons of `lessons` left after taking out every lesson of `other` once.
+fn without<'a>(lessons: &'a [Lesson], other: &[Lesson]) -> Vec<&'a Lesson> {
+  let mut other = other.iter().map(|x| x.uid_bytes()).collect::<Vec<_>>();
+  lessons
+    .iter()
+    .filter(|x| match other.iter().position(|o| *o == x.uid_bytes()) {
+      Some(i) => {
+        other.swap_remove(i);
+        false
+      }
+      None => true,
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::{Duration, TimeZone, Utc};
+
+  use super::{timeline, LessonChange};
+  use crate::{Group, Lesson, Num, Snapshot};
+
+  fn snapshot(hour: i64, groups: &[(&str, &[(&str, &str)])]) -> Snapshot {
+    let date = Utc.with_ymd_and_hms(2023, 9, 11, 0, 0, 0).unwrap();
+    let groups = groups
+      .iter()
+      .map(|(name, lessons)| Group {
+        lessons: lessons
+          .iter()
+          .map(|(num, name)| Lesson { num: Num::Actual(num.to_string()), name: name.to_string(), ..Default::default() })
+          .collect(),
+        ..Group::new(name.to_string())
+      })
+      .collect();
+    let mut snapshot = Snapshot::new(groups, date);
+    snapshot.parsed_date = date + Duration::hours(hour);
+    snapshot
+  }
+
+  #[test]
+  fn tracks_group_versions() {
+    let snapshots = [
+      snapshot(9, &[("Ит1-22", &[("1", "Математика"), ("2", "Химия")]), ("Ир1-22", &[("1", "История")])]),
+      snapshot(7, &[("Ир1-22", &[("1", "Физика")])]),
+      snapshot(8, &[("Ит1-22", &[("1", "Математика"), ("2", "Физика")]), ("Ир1-22", &[("1", "Физика")])]),
+      snapshot(10, &[("Ит1-22", &[("2", "Химия"), ("1", "Математика")]), ("Ир1-22", &[("1", "Физика")])]),
+      snapshot(11, &[("Ир1-22", &[("1", "Физика")])]),
+    ];
+    let date = snapshots[0].date.date_naive();
+    let versions = timeline(&snapshots, date, "Ит1-22");
+
+    assert_eq!(
+      versions.iter().map(|v| v.parsed_date).collect::<Vec<_>>(),
+      [8, 9, 11].map(|h| snapshots[2].date + Duration::hours(h))
+    );
+    assert_eq!(versions[0].changes.len(), 2);
+    assert!(
+      matches!(versions[1].changes[..], [LessonChange::Changed { ref before, ref after }] if before.name == "Физика" && after.name == "Химия")
+    );
+    assert!(versions[2].uid.is_none());
+    assert_eq!(versions[2].changes.len(), 2);
+    assert!(timeline(&snapshots, date, "Са1-22").is_empty());
+  }
+}
@@ -1,18 +1,71 @@
+use sha2::{digest::Digest, Sha256};
+
 static ALPHABET: &[u8] = "0123456789abcdefghijklmnopqrstuvwxyz".as_bytes();
 
+/// Lowercase Crockford base32: no `i`, `l`, `o` and `u`, so ids survive being read aloud or retyped.
+static BASE32: &[u8] = "0123456789abcdefghjkmnpqrstvwxyz".as_bytes();
+
+/// Length of the ids written into `uid` fields. 16 characters keep 80 bits of the hash.
+pub const UID_LEN: usize = 16;
+
+/// The longest id a 32 byte hash can back, every character takes 5 bits.
+pub const MAX_UID_LEN: usize = 256 / 5;
+
+/// Little-endian bytes of `$n` taken as `$tt`.
 #[macro_export]
 macro_rules! num_as_bytes {
   ($n: expr, $tt:ty) => {{
-    let mut res = [0u8; (<$tt>::BITS / 8) as usize];
-    let mut n = $n;
-    for i in 0..(<$tt>::BITS / 8) as usize {
-      res[i] = ((n >> (8 * i)) & 0xff) as u8;
-      n = n >> i;
+    <$tt>::to_le_bytes($n as $tt)
+  }};
+}
+
+/// SHA-256 over a canonical encoding of fields: every field is prefixed with its length, so `"ab" + "c"`
+/// and `"a" + "bc"` hash differently, and optional fields carry a presence tag.
+pub(crate) struct FieldHasher(Sha256);
+
+impl FieldHasher {
+  pub fn new(domain: &str) -> Self {
+    let mut hasher = FieldHasher(Sha256::new());
+    hasher.field(domain.as_bytes());
+    hasher
+  }
+
+  pub fn field(&mut self, bytes: &[u8]) -> &mut Self {
+    self.0.update((bytes.len() as u64).to_le_bytes());
+    self.0.update(bytes);
+    self
+  }
+
+  pub fn str(&mut self, value: &str) -> &mut Self {
+    self.field(value.as_bytes())
+  }
+
+  pub fn opt_str(&mut self, value: Option<&str>) -> &mut Self {
+    match value {
+      Some(x) => self.field(&[1]).str(x),
+      None => self.field(&[0]),
     }
+  }
+
+  pub fn finish(&mut self) -> [u8; 32] {
+    let mut res = [0u8; 32];
+    self.0.finalize_into_reset((&mut res).into());
     res
-  }};
+  }
+}
+
+/// Takes the first `len * 5` bits of `bytes` as base32. Each character maps exactly 5 bits, so every
+/// character of the alphabet is equally likely. `len` is capped by the number of available bits.
+pub fn encode_uid(bytes: &[u8], len: usize) -> String {
+  let len = len.min(bytes.len() * 8 / 5);
+  let bit = |i: usize| (bytes[i / 8] >> (7 - i % 8)) & 1;
+  (0..len)
+    .map(|c| (0..5).fold(0usize, |acc, b| acc << 1 | bit(c * 5 + b) as usize))
+    .map(|x| BASE32[x] as char)
+    .collect()
 }
 
+/// The id encoding used before [`encode_uid`]. Kept to match uids of stored snapshots.
 pub(crate) fn bytes_as_str(bytes: &[u8]) -> String {
   let len = ALPHABET.len();
   let mut res = String::new();
@@ -29,16 +82,34 @@ pub(crate) fn bytes_as_str(bytes: &[u8]) -> String {
 pub mod time {
   use chrono::{DateTime, Datelike, Duration, Timelike, Utc};
 
+  #[cfg(feature = "frozen-clock")]
+  static FROZEN: std::sync::RwLock<Option<DateTime<Utc>>> = std::sync::RwLock::new(None);
+
+  /// Pins the clock used by [`now`] and everything derived from it. Only for tests that need
+  /// reproducible output, `None` lets the clock run again.
+  #[cfg(feature = "frozen-clock")]
+  pub fn freeze(at: Option<DateTime<Utc>>) {
+    *FROZEN.write().unwrap_or_else(|e| e.into_inner()) = at;
+  }
+
   pub fn now_with_offset(offset_days: i64) -> DateTime<Utc> {
     now() + Duration::days(offset_days)
   }
 
   pub fn now() -> DateTime<Utc> {
+    #[cfg(feature = "frozen-clock")]
+    if let Some(frozen) = *FROZEN.read().unwrap_or_else(|e| e.into_inner()) {
+      return frozen;
+    }
     Utc::now() + Duration::hours(3)
   }
 
   pub fn now_date() -> DateTime<Utc> {
-    now()
+    date_of(now())
+  }
+
+  pub fn date_of(date: DateTime<Utc>) -> DateTime<Utc> {
+    date
       .with_hour(0)
       .unwrap()
       .with_minute(0)
@@ -57,3 +128,68 @@ pub mod time {
     date.iso_week().week0() % 2 == 0
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::{encode_uid, MAX_UID_LEN};
+  use crate::{legacy::LegacyUid, Lesson, Snapshot, Uid};
+
+  #[test]
+  fn encodes_every_five_bits() {
+    assert_eq!(encode_uid(&[0b00001_000, 0b10_00011_0, 0xff], 4), "123f");
+    assert_eq!(encode_uid(&[0xff; 32], 100).len(), MAX_UID_LEN);
+    assert!(encode_uid(&[0xff; 32], MAX_UID_LEN).chars().all(|c| c == 'z'));
+  }
+
+  #[test]
+  fn nums_are_little_endian() {
+    assert_eq!(num_as_bytes!(0xab, u8), [0xab]);
+    assert_eq!(num_as_bytes!(0x0102, u16), [2, 1]);
+    assert_eq!(num_as_bytes!(0x0102_0304, u32), [4, 3, 2, 1]);
+    assert_eq!(num_as_bytes!(0x0102_0304_0506_0708u64, u64), 0x0102_0304_0506_0708u64.to_le_bytes());
+    assert_eq!(num_as_bytes!(u32::MAX - 1, u32), [0xfe, 0xff, 0xff, 0xff]);
+  }
+
+  #[test]
+  fn fields_are_separated() {
+    let lesson = |name: &str, teacher: &str| Lesson { name: name.into(), teacher: Some(teacher.into()), ..Default::default() };
+    let (a, b) = (lesson("Физика", "Иванов"), lesson("ИвановФизика", ""));
+    assert_eq!(a.legacy_uid(), b.legacy_uid());
+    assert_ne!(a.uid_bytes(), b.uid_bytes());
+
+    let no_teacher = Lesson { teacher: None, ..lesson("Физика", "") };
+    assert_ne!(no_teacher.uid_bytes(), lesson("Физика", "").uid_bytes());
+  }
+
+  #[test]
+  fn notes_change_the_uid() {
+    let group = crate::Group {
+      lessons: vec![Lesson { name: "Математика".into(), ..Default::default() }],
+      ..crate::Group::new("Ит1-22".into())
+    };
+    let snapshot = Snapshot::new(vec![group], crate::utils::time::now());
+    let mut noted = snapshot.clone();
+    noted
+      .notes
+      .push(crate::Notice { text: "Классный час в 13:00".into(), groups: vec![] });
+    noted.refresh();
+    assert_ne!(snapshot.uid, noted.uid);
+
+    let mut discovered = snapshot.clone();
+    discovered.discovered_groups.push("Ит2-22".into());
+    discovered.refresh();
+    assert_ne!(snapshot.uid, discovered.uid);
+    assert_eq!(snapshot.tiny("Ит1-22").uid_bytes(), snapshot.tiny("Ит1-22").uid_bytes());
+    assert_ne!(snapshot.tiny("Ит1-22").uid_bytes(), noted.tiny("Ит1-22").uid_bytes());
+  }
+
+  #[test]
+  fn legacy_uid_is_kept() {
+    let lesson = Lesson { name: "Математика".into(), classroom: Some("305".into()), ..Default::default() };
+    let mut group = crate::Group::new("Ит1-22".into());
+    group.lessons.push(lesson);
+    let snapshot = Snapshot::new(vec![group], crate::utils::time::now());
+    assert_eq!(snapshot.legacy_uid(), "tb4yhd5boq");
+    assert_eq!(snapshot.uid.len(), super::UID_LEN);
+  }
+}
@@ -0,0 +1,32 @@
+use maiq_parser::{
+  env,
+  fetch::{Fetcher, FetcherConfig},
+  server::{poll, router, AppState},
+  store::SnapshotStore,
+  warmup_defaults,
+};
+
+#[tokio::main]
+async fn main() {
+  dotenvy::dotenv().ok();
+  pretty_env_logger::init();
+  env::init();
+  env::server::init();
+  warmup_defaults();
+
+  let addr: std::net::SocketAddr = env::server::server_addr().into();
+  let store =
+    Option::<String>::from(env::snapshots_dir()).map(|dir| SnapshotStore::new(dir).expect("unable to open snapshots dir"));
+
+  let fetcher = Fetcher::with_config(FetcherConfig { proxy: env::server::fetch_proxy().into(), ..Default::default() })
+    .expect("unable to build http client");
+
+  let state = AppState::new(fetcher, store);
+  tokio::spawn(poll(state.clone(), env::server::poll_interval().into()));
+
+  log::info!("Listening on {}", addr);
+  axum::Server::bind(&addr)
+    .serve(router(state).into_make_service())
+    .await
+    .expect("server error");
+}
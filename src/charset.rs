@@ -0,0 +1,86 @@
+use encoding_rs::{Encoding, KOI8_R, UTF_8, WINDOWS_1251};
+
+const META_SCAN_LIMIT: usize = 4096;
+
+pub fn decode(bytes: &[u8], content_type: Option<&str>) -> (String, &'static Encoding) {
+  let encoding = detect(bytes, content_type);
+  let (html, _, _) = encoding.decode(bytes);
+  (html.into_owned(), encoding)
+}
+
+pub fn detect(bytes: &[u8], content_type: Option<&str>) -> &'static Encoding {
+  if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+    return encoding;
+  }
+
+  content_type
+    .and_then(charset_label)
+    .or_else(|| meta_charset(bytes))
+    .unwrap_or_else(|| guess(bytes))
+}
+
+fn charset_label(raw: &str) -> Option<&'static Encoding> {
+  let lower = raw.to_ascii_lowercase();
+  let (_, rest) = lower.split_once("charset=")?;
+  let label = rest
+    .trim_start_matches(['"', '\''])
+    .split(['"', '\'', ';', ' ', '>', '/'])
+    .next()?;
+  Encoding::for_label(label.as_bytes())
+}
+
+fn meta_charset(bytes: &[u8]) -> Option<&'static Encoding> {
+  let head = String::from_utf8_lossy(&bytes[..bytes.len().min(META_SCAN_LIMIT)]);
+  head
+    .to_ascii_lowercase()
+    .split("<meta")
+    .skip(1)
+    .find_map(|tag| charset_label(tag.split('>').next()?))
+}
+
+fn guess(bytes: &[u8]) -> &'static Encoding {
+  if !bytes.is_ascii() && std::str::from_utf8(bytes).is_ok() {
+    return UTF_8;
+  }
+
+  // Russian text is mostly lowercase. In CP1251 lowercase letters take the upper half of the
+  // cyrillic range, in KOI8-R it is the other way around
+  let upper_half = bytes.iter().filter(|b| **b >= 0xe0).count();
+  let lower_half = bytes.iter().filter(|b| (0xc0..0xe0).contains(*b)).count();
+  match lower_half > upper_half {
+    true => KOI8_R,
+    false => WINDOWS_1251,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{decode, detect};
+  use encoding_rs::{KOI8_R, UTF_8, WINDOWS_1251};
+
+  const TEXT: &str = "Расписание занятий на понедельник";
+
+  #[test]
+  fn prefers_header_over_meta() {
+    let (bytes, _, _) = UTF_8.encode(TEXT);
+    let html = [b"<meta charset=\"windows-1251\">".as_slice(), &bytes].concat();
+    assert_eq!(detect(&html, Some("text/html; charset=UTF-8")), UTF_8);
+    assert_eq!(detect(&html, Some("text/html")), WINDOWS_1251);
+  }
+
+  #[test]
+  fn reads_http_equiv_meta() {
+    let html = b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=koi8-r\">";
+    assert_eq!(detect(html, None), KOI8_R);
+  }
+
+  #[test]
+  fn guesses_without_hints() {
+    for encoding in [UTF_8, WINDOWS_1251, KOI8_R] {
+      let (bytes, _, _) = encoding.encode(TEXT);
+      let (text, detected) = decode(&bytes, None);
+      assert_eq!(detected, encoding);
+      assert_eq!(text, TEXT);
+    }
+  }
+}
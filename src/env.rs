@@ -1,4 +1,4 @@
-use std::str::FromStr;
+use std::{net::SocketAddr, str::FromStr, time::Duration};
 
 use lazy_static::lazy_static;
 
@@ -38,14 +38,14 @@ macro_rules! env_default {
 macro_rules! vars {
   {$($getter: ident ($var_name: ident) -> $ty: tt),*} => {
     lazy_static! {
-      $(static ref $var_name: $ty = self::parse_var::<$ty>(stringify!($var_name));)*
+      $(static ref $var_name: $ty = $crate::env::parse_var::<$ty>(stringify!($var_name));)*
     }
 
     $(pub fn $getter() -> $ty { $var_name.clone() })*
 
     pub fn init() {
       $(
-        self::var(stringify!($var_name))
+        $crate::env::var(stringify!($var_name))
           .and_then(|x| x.parse::<$ty>().ok())
           .is_none()
           .then(|| {
@@ -68,13 +68,37 @@ pub fn parse_var<T: FromStr + Default>(var: &'static str) -> T {
 }
 
 env_params! {
-  Vec<String> as Strings { |s: &str| -> Result<Vec<String>, ()> { Ok(s.split(';').map(|s| s.trim().into()).collect::<Vec<String>>()) } }
+  Vec<String> as Strings { |s: &str| -> Result<Vec<String>, ()> { Ok(s.split(';').map(|s| s.trim().into()).collect::<Vec<String>>()) } },
+  bool as Flag { |s: &str| -> Result<bool, ()> { Ok(matches!(s.trim(), "1" | "true" | "yes")) } },
+  Option<String> as OptionalString { |s: &str| -> Result<Option<String>, ()> { Ok(Some(s.trim().to_string()).filter(|x| !x.is_empty())) } },
+  SocketAddr as Addr { |s: &str| s.trim().parse::<SocketAddr>() },
+  Duration as Seconds { |s: &str| s.trim().parse::<u64>().map(Duration::from_secs) }
 }
 
 env_default! {
-  Strings => vec![]
+  Strings => vec![],
+  Flag => false,
+  OptionalString => None,
+  Addr => SocketAddr::from(([0, 0, 0, 0], 8080)),
+  Seconds => Duration::from_secs(60)
 }
 
 vars! {
-  groups(GROUPS) -> Strings
+  groups(GROUPS) -> Strings,
+  discover_groups(DISCOVER_GROUPS) -> Flag,
+  snapshots_dir(SNAPSHOTS_DIR) -> OptionalString
+}
+
+/// Settings only `maiq-server` reads, kept apart so the CLI doesn't report them missing.
+#[cfg(feature = "server")]
+pub mod server {
+  use lazy_static::lazy_static;
+
+  use super::{Addr, OptionalString, Seconds};
+
+  vars! {
+    server_addr(SERVER_ADDR) -> Addr,
+    poll_interval(POLL_INTERVAL) -> Seconds,
+    fetch_proxy(FETCH_PROXY) -> OptionalString
+  }
 }
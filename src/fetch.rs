@@ -0,0 +1,374 @@
+use std::{
+  collections::{hash_map::DefaultHasher, HashMap},
+  hash::{Hash, Hasher},
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use log::{debug, warn};
+use maiq_shared::{FetchUrl, Snapshot};
+use reqwest::{
+  header::{HeaderName, CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+  Client, Proxy, RequestBuilder, Response, StatusCode,
+};
+
+use crate::source::{RawPage, ScheduleSource};
+
+lazy_static! {
+  static ref DEFAULT_FETCHER: Fetcher = Fetcher::new();
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+  etag: Option<String>,
+  last_modified: Option<String>,
+  hash: u64,
+  snapshot: Snapshot,
+}
+
+/// Remembers validators and content hash of the last response per url.
+#[derive(Debug, Default, Clone)]
+pub struct FetchCache {
+  entries: HashMap<&'static str, CacheEntry>,
+}
+
+impl FetchCache {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn get<T: FetchUrl>(&self, mode: &T) -> Option<&Snapshot> {
+    self.entries.get(mode.url()).map(|e| &e.snapshot)
+  }
+
+  pub fn clear(&mut self) {
+    self.entries.clear()
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct FetcherConfig {
+  pub timeout: Duration,
+  pub connect_timeout: Duration,
+  pub retries: u32,
+  pub retry_delay: Duration,
+  pub user_agent: String,
+  pub proxy: Option<String>,
+  pub max_body_size: usize,
+}
+
+impl Default for FetcherConfig {
+  fn default() -> Self {
+    Self {
+      timeout: Duration::from_secs(15),
+      connect_timeout: Duration::from_secs(5),
+      retries: 3,
+      retry_delay: Duration::from_millis(500),
+      user_agent: concat!("maiq-parser/", env!("CARGO_PKG_VERSION")).into(),
+      proxy: None,
+      max_body_size: 4 * 1024 * 1024,
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Fetcher {
+  client: Client,
+  config: FetcherConfig,
+}
+
+#[derive(Debug)]
+enum Attempt {
+  Retry(anyhow::Error),
+  Fail(anyhow::Error),
+}
+
+impl Default for Fetcher {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Fetcher {
+  pub fn new() -> Self {
+    Self::with_config(FetcherConfig::default()).expect("Unable to build default http client")
+  }
+
+  pub fn with_config(config: FetcherConfig) -> anyhow::Result<Self> {
+    let mut builder = Client::builder()
+      .timeout(config.timeout)
+      .connect_timeout(config.connect_timeout)
+      .user_agent(config.user_agent.clone());
+
+    if let Some(ref proxy) = config.proxy {
+      builder = builder.proxy(Proxy::all(proxy)?);
+    }
+
+    Ok(Self { client: builder.build()?, config })
+  }
+
+  pub fn config(&self) -> &FetcherConfig {
+    &self.config
+  }
+
+  pub async fn fetch<T: FetchUrl>(&self, mode: &T) -> anyhow::Result<String> {
+    Ok(self.page(mode.url()).await?.decode().0)
+  }
+
+  /// What [`crate::snapshot_from_remote`] does with the default fetcher, for callers that bring their own
+  /// client, proxy or base url.
+  pub async fn snapshot<T: FetchUrl>(&self, mode: &T) -> anyhow::Result<Snapshot> {
+    self.page(mode.url()).await?.snapshot(mode.date())
+  }
+
+  pub async fn snapshot_cached<T: FetchUrl>(&self, mode: &T, cache: &mut FetchCache) -> anyhow::Result<Snapshot> {
+    let url = mode.url();
+    let entry = cache.entries.get(url);
+    let res = self
+      .send(|| {
+        let mut req = self.client.get(url);
+        if let Some(etag) = entry.and_then(|e| e.etag.as_ref()) {
+          req = req.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = entry.and_then(|e| e.last_modified.as_ref()) {
+          req = req.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        req
+      })
+      .await?;
+
+    if res.status() == StatusCode::NOT_MODIFIED {
+      return match entry {
+        Some(entry) => {
+          debug!("{} is not modified", url);
+          Ok(entry.snapshot.clone())
+        }
+        None => Err(anyhow::anyhow!("{} responded with 304 while nothing is cached", url)),
+      };
+    }
+
+    let page = self.read_page(res.error_for_status()?).await?;
+    let hash = hash(&page.bytes);
+
+    if let Some(entry) = cache.entries.get_mut(url).filter(|e| e.hash == hash) {
+      debug!("{} has the same content", url);
+      entry.etag = page.etag;
+      entry.last_modified = page.last_modified;
+      return Ok(entry.snapshot.clone());
+    }
+
+    let snapshot = page.snapshot(mode.date())?;
+    cache
+      .entries
+      .insert(url, CacheEntry { etag: page.etag, last_modified: page.last_modified, hash, snapshot: snapshot.clone() });
+    Ok(snapshot)
+  }
+
+  async fn page(&self, url: &str) -> anyhow::Result<RawPage> {
+    let res = self.send(|| self.client.get(url)).await?.error_for_status()?;
+    self.read_page(res).await
+  }
+
+  async fn read_page(&self, res: Response) -> anyhow::Result<RawPage> {
+    let content_type = header(&res, CONTENT_TYPE);
+    let etag = header(&res, ETAG);
+    let last_modified = header(&res, LAST_MODIFIED);
+    Ok(RawPage { bytes: self.read_body(res).await?, content_type, etag, last_modified })
+  }
+
+  async fn send<F: Fn() -> RequestBuilder>(&self, make_request: F) -> anyhow::Result<Response> {
+    let mut attempt = 0;
+    loop {
+      let result = match make_request().send().await {
+        Ok(res) if res.status().is_server_error() => Attempt::Retry(anyhow::anyhow!("Server responded with {}", res.status())),
+        Ok(res) => return Ok(res),
+        Err(e) if e.is_timeout() || e.is_connect() || e.is_request() => Attempt::Retry(e.into()),
+        Err(e) => Attempt::Fail(e.into()),
+      };
+
+      match result {
+        Attempt::Retry(e) if attempt < self.config.retries => {
+          let delay = self.backoff(attempt);
+          warn!("Request failed: {}. Retrying in {:?}", e, delay);
+          tokio::time::sleep(delay).await;
+          attempt += 1;
+        }
+        Attempt::Retry(e) | Attempt::Fail(e) => return Err(e),
+      }
+    }
+  }
+
+  fn backoff(&self, attempt: u32) -> Duration {
+    let base = self.config.retry_delay * 2u32.pow(attempt);
+    let nanos = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|x| x.subsec_nanos())
+      .unwrap_or_default();
+    let jitter = base.as_millis() as u64 / 2;
+    base + Duration::from_millis(nanos as u64 % (jitter + 1))
+  }
+
+  async fn read_body(&self, mut res: Response) -> anyhow::Result<Vec<u8>> {
+    let max = self.config.max_body_size;
+    let too_large = || anyhow::anyhow!("Response body exceeds {} bytes", max);
+    if header(&res, CONTENT_LENGTH)
+      .and_then(|x| x.parse::<usize>().ok())
+      .map(|len| len > max)
+      .unwrap_or(false)
+    {
+      return Err(too_large());
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = res.chunk().await? {
+      if body.len() + chunk.len() > max {
+        return Err(too_large());
+      }
+      body.extend_from_slice(&chunk);
+    }
+
+    Ok(body)
+  }
+}
+
+#[async_trait]
+impl ScheduleSource for Fetcher {
+  async fn fetch_raw(&self, target: &(dyn FetchUrl + Sync)) -> anyhow::Result<RawPage> {
+    self.page(target.url()).await
+  }
+}
+
+pub async fn snapshot_from_remote_cached<T: FetchUrl>(mode: &T, cache: &mut FetchCache) -> anyhow::Result<Snapshot> {
+  DEFAULT_FETCHER.snapshot_cached(mode, cache).await
+}
+
+pub(crate) fn default_fetcher() -> &'static Fetcher {
+  &DEFAULT_FETCHER
+}
+
+fn header(res: &Response, name: HeaderName) -> Option<String> {
+  res
+    .headers()
+    .get(name)
+    .and_then(|x| x.to_str().ok())
+    .map(|x| x.to_string())
+}
+
+fn hash(bytes: &[u8]) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  bytes.hash(&mut hasher);
+  hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+  };
+
+  use chrono::{DateTime, Utc};
+  use maiq_shared::{utils::time::now, FetchUrl};
+  use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+  };
+
+  use super::{FetchCache, Fetcher, FetcherConfig};
+
+  const PAGE: &str = "<table><tr><td>Группа</td><td>Пара</td><td>Дисциплина</td><td>Ауд.</td></tr>\
+    <tr><td>Ит1-22</td><td>1</td><td>Математика</td><td>305</td></tr></table>";
+
+  struct Mock(&'static str);
+
+  impl FetchUrl for Mock {
+    fn url(&self) -> &'static str {
+      self.0
+    }
+
+    fn date(&self) -> DateTime<Utc> {
+      now()
+    }
+  }
+
+  /// Answers with `(status, extra headers, body)` in a loop and records the head of every request.
+  async fn serve(responses: Vec<(&'static str, &'static str, &'static str)>) -> (Mock, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/today.htm", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(vec![]));
+    let log = requests.clone();
+
+    tokio::spawn(async move {
+      for (status, headers, body) in responses.into_iter().cycle() {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut buf = [0u8; 1024];
+        let len = socket.read(&mut buf).await.unwrap_or_default();
+        log
+          .lock()
+          .unwrap()
+          .push(String::from_utf8_lossy(&buf[..len]).to_lowercase());
+        let res =
+          format!("HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}", status, headers, body.len(), body);
+        let _ = socket.write_all(res.as_bytes()).await;
+      }
+    });
+
+    (Mock(Box::leak(url.into_boxed_str())), requests)
+  }
+
+  fn fetcher(config: FetcherConfig) -> Fetcher {
+    Fetcher::with_config(FetcherConfig { retry_delay: Duration::from_millis(1), ..config }).unwrap()
+  }
+
+  #[tokio::test]
+  async fn retries_server_errors() {
+    let (mock, requests) = serve(vec![("500 Internal Server Error", "", ""), ("200 OK", "", "<table></table>")]).await;
+    let body = fetcher(FetcherConfig::default()).fetch(&mock).await.unwrap();
+    assert_eq!(body, "<table></table>");
+    assert_eq!(requests.lock().unwrap().len(), 2);
+  }
+
+  #[tokio::test]
+  async fn gives_up_after_retries() {
+    let (mock, requests) = serve(vec![("503 Service Unavailable", "", "")]).await;
+    let config = FetcherConfig { retries: 2, ..Default::default() };
+    assert!(fetcher(config).fetch(&mock).await.is_err());
+    assert_eq!(requests.lock().unwrap().len(), 3);
+  }
+
+  #[tokio::test]
+  async fn rejects_large_bodies() {
+    let (mock, _) = serve(vec![("200 OK", "", "0123456789")]).await;
+    let config = FetcherConfig { max_body_size: 4, ..Default::default() };
+    assert!(fetcher(config).fetch(&mock).await.is_err());
+  }
+
+  #[tokio::test]
+  async fn sends_cached_validators() {
+    let (mock, requests) =
+      serve(vec![("200 OK", "ETag: \"v1\"\r\nContent-Type: text/html; charset=utf-8\r\n", PAGE), ("304 Not Modified", "", "")])
+        .await;
+    let fetcher = fetcher(FetcherConfig::default());
+    let mut cache = FetchCache::new();
+
+    let first = fetcher.snapshot_cached(&mock, &mut cache).await.unwrap();
+    assert!(first.group("Ит1-22").is_some());
+    let second = fetcher.snapshot_cached(&mock, &mut cache).await.unwrap();
+    assert_eq!(first.uid, second.uid);
+
+    let requests = requests.lock().unwrap();
+    assert!(!requests[0].contains("if-none-match"));
+    assert!(requests[1].contains("if-none-match: \"v1\""));
+  }
+
+  #[tokio::test]
+  async fn not_modified_without_cache_fails() {
+    let (mock, _) = serve(vec![("304 Not Modified", "", "")]).await;
+    let mut cache = FetchCache::new();
+    assert!(fetcher(FetcherConfig::default())
+      .snapshot_cached(&mock, &mut cache)
+      .await
+      .is_err());
+    assert!(cache.get(&mock).is_none());
+  }
+}
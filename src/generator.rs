@@ -0,0 +1,274 @@
+use chrono::{DateTime, Datelike, Utc};
+use encoding_rs::WINDOWS_1251;
+use maiq_shared::{default::DefaultDay, utils::time, Group, Lesson, LessonSource, Num, Snapshot};
+
+use crate::{
+  parser::{date::MONTHS, replace::from_default},
+  source::RawPage,
+};
+
+const WEEKDAYS: [&str; 7] = ["понедельник", "вторник", "среда", "четверг", "пятница", "суббота", "воскресенье"];
+const HEAD: &str = r#"<html>
+<head>
+<meta http-equiv=Content-Type content="text/html; charset=windows-1251">
+<title>Изменения в расписании</title>
+</head>
+<body lang=RU>
+<div class=WordSection1>
+"#;
+const TAIL: &str = "</div>\n</body>\n</html>\n";
+
+struct Entry<'a> {
+  nums: Vec<&'a str>,
+  lesson: &'a Lesson,
+}
+
+/// Renders a snapshot the way the college site lays it out: a date header, notes and a table where
+/// group cells span all rows of the group and a num shared by several subgroups is written once.
+/// Cell text is written as is, the parser does not decode entities either.
+pub fn render_snapshot(snapshot: &Snapshot) -> String {
+  let mut html = String::from(HEAD);
+  html += &format!(
+    "<table class=MsoNormalTable border=0>\n <tr><td><p class=MsoNormal align=center><b>Изменения в расписании на {} {} {} г. ({})</b></p></td></tr>\n</table>\n",
+    snapshot.date.day(),
+    MONTHS[snapshot.date.month0() as usize],
+    snapshot.date.year(),
+    WEEKDAYS[snapshot.date.weekday().num_days_from_monday() as usize]
+  );
+
+  for note in snapshot.notes.iter() {
+    html += &format!("<p class=MsoNormal>{}</p>\n", note.text);
+  }
+
+  html += "<table class=MsoTableGrid border=1 cellspacing=0 cellpadding=0>\n";
+  html += " <tr><td>Группа</td><td>№ пары</td><td>Дисциплина, преподаватель</td><td>Ауд.</td></tr>\n";
+  snapshot
+    .groups
+    .iter()
+    .for_each(|group| render_group(group, &mut html));
+  html += "</table>\n";
+  html += TAIL;
+  html
+}
+
+/// Builds a snapshot out of the default timetable, taking week parity from the date.
+pub fn snapshot_from_default(day: &DefaultDay, date: DateTime<Utc>) -> Snapshot {
+  let is_even = time::is_week_even(&date);
+  let groups = day
+    .groups
+    .iter()
+    .map(|g| Group {
+      uid: "EMPTY".into(),
+      name: g.name.clone(),
+      lessons: g
+        .lessons
+        .iter()
+        .filter(|l| l.is_even.map(|e| e == is_even).unwrap_or(true))
+        // Rendered as if the page listed them, otherwise every row would read "По расписанию"
+        .map(|l| Lesson { source: LessonSource::Page, ..from_default(l) })
+        .collect(),
+    })
+    .filter(|g| !g.lessons.is_empty())
+    .collect();
+  Snapshot::new(groups, date)
+}
+
+pub fn render_default(day: &DefaultDay, date: DateTime<Utc>) -> String {
+  render_snapshot(&snapshot_from_default(day, date))
+}
+
+pub fn encode(html: &str) -> Vec<u8> {
+  let (bytes, _, _) = WINDOWS_1251.encode(html);
+  bytes.into_owned()
+}
+
+/// Rendered and CP1251 encoded page, ready to be served through a `MemorySource`.
+pub fn render_page(snapshot: &Snapshot) -> RawPage {
+  RawPage {
+    bytes: encode(&render_snapshot(snapshot)),
+    content_type: Some("text/html; charset=windows-1251".into()),
+    ..Default::default()
+  }
+}
+
+fn render_group(group: &Group, html: &mut String) {
+  let entries = merge_nums(&group.lessons);
+  let mut i = 0;
+  let mut num_span = 0;
+
+  while i < entries.len() {
+    let run = entries[i..]
+      .iter()
+      .take_while(|e| e.lesson.subgroup == entries[i].lesson.subgroup)
+      .count();
+    let group_cell = match entries[i].lesson.subgroup {
+      Some(sub) => format!("{} {}", group.name, sub),
+      None => group.name.clone(),
+    };
+
+    for (offset, entry) in entries[i..i + run].iter().enumerate() {
+      *html += " <tr>";
+      if offset == 0 {
+        *html += &format!("<td rowspan={}>{}</td>", run, group_cell);
+      }
+
+      if num_span > 0 {
+        num_span -= 1;
+      } else {
+        // A num cell can only be merged into rows that start with a group cell, otherwise the
+        // next cell would be read as a num
+        num_span = entries[i + offset + 1..]
+          .iter()
+          .take_while(|next| next.nums == entry.nums && next.lesson.subgroup != entry.lesson.subgroup)
+          .count()
+          .min(usize::from(offset + 1 == run));
+        *html += &format!("<td rowspan={}>{}</td>", num_span + 1, entry.nums.join(","));
+      }
+
+      *html += &format!("<td>{}</td><td>{}</td></tr>\n", lesson_cell(entry.lesson), classroom_cell(entry.lesson));
+    }
+    i += run;
+  }
+}
+
+fn merge_nums(lessons: &[Lesson]) -> Vec<Entry<'_>> {
+  let mut entries: Vec<Entry> = vec![];
+  for lesson in lessons.iter() {
+    let num = match lesson.num {
+      Num::Actual(ref x) => x.as_str(),
+      _ => "",
+    };
+
+    match entries.last_mut() {
+      Some(last) if is_same(last.lesson, lesson) && !num.is_empty() && !last.nums.contains(&"") => last.nums.push(num),
+      _ => entries.push(Entry { nums: vec![num], lesson }),
+    }
+  }
+  entries
+}
+
+fn is_same(a: &Lesson, b: &Lesson) -> bool {
+  a.name == b.name && a.teacher == b.teacher && a.classroom == b.classroom && a.subgroup == b.subgroup && a.status == b.status
+}
+
+fn lesson_cell(lesson: &Lesson) -> String {
+  if lesson.is_cancelled() {
+    return "Нет".into();
+  }
+
+  match (lesson.source, lesson.teacher.as_ref()) {
+    (LessonSource::Default | LessonSource::DefaultWithPageClassroom, _) => "По расписанию".into(),
+    (LessonSource::Page, Some(teacher)) => format!("{}, {}", lesson.name, teacher),
+    (LessonSource::Page, None) => lesson.name.clone(),
+  }
+}
+
+fn classroom_cell(lesson: &Lesson) -> &str {
+  match lesson.source {
+    LessonSource::Default => "",
+    _ => lesson.classroom.as_deref().unwrap_or(""),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::{TimeZone, Utc, Weekday};
+  use encoding_rs::WINDOWS_1251;
+  use maiq_shared::{
+    default::{DefaultDay, DefaultGroup, DefaultLesson},
+    Group, Lesson, LessonSource, LessonStatus, Notice, Num, Snapshot,
+  };
+
+  use super::{render_page, render_snapshot, snapshot_from_default};
+
+  fn lesson(num: &str, subgroup: Option<u8>, name: &str, teacher: Option<&str>, classroom: Option<&str>) -> Lesson {
+    Lesson {
+      num: Num::Actual(num.into()),
+      name: name.into(),
+      subgroup,
+      teacher: teacher.map(Into::into),
+      classroom: classroom.map(Into::into),
+      ..Default::default()
+    }
+  }
+
+  fn snapshot(lessons: Vec<Lesson>) -> Snapshot {
+    Snapshot::new(vec![Group { lessons, ..Group::new("Ит1-22".into()) }], Utc.with_ymd_and_hms(2023, 9, 11, 0, 0, 0).unwrap())
+  }
+
+  #[test]
+  fn renders_subgroups() {
+    let html = render_snapshot(&snapshot(vec![
+      lesson("1", Some(1), "Математика", Some("Иванов"), Some("305")),
+      lesson("1", Some(2), "Физика", Some("Петров"), Some("306")),
+      lesson("2", None, "История", None, None),
+    ]));
+
+    assert!(html.contains(" <tr><td rowspan=1>Ит1-22 1</td><td rowspan=2>1</td><td>Математика, Иванов</td><td>305</td></tr>\n"));
+    assert!(html.contains(" <tr><td rowspan=1>Ит1-22 2</td><td>Физика, Петров</td><td>306</td></tr>\n"));
+    assert!(html.contains(" <tr><td rowspan=1>Ит1-22</td><td rowspan=1>2</td><td>История</td><td></td></tr>\n"));
+  }
+
+  #[test]
+  fn renders_cancelled_lessons() {
+    let cancelled =
+      |num| Lesson { status: LessonStatus::Cancelled, ..lesson(num, None, "Математика", None, Some("305")) };
+    let html =
+      render_snapshot(&snapshot(vec![cancelled("1"), cancelled("2"), lesson("3", None, "Математика", None, Some("305"))]));
+
+    assert!(html.contains(" <tr><td rowspan=2>Ит1-22</td><td rowspan=1>1,2</td><td>Нет</td><td>305</td></tr>\n"));
+    assert!(html.contains(" <tr><td rowspan=1>3</td><td>Математика</td><td>305</td></tr>\n"));
+  }
+
+  #[test]
+  fn renders_notes_before_the_table() {
+    let mut snapshot = snapshot(vec![lesson("1", None, "Математика", None, None)]);
+    snapshot
+      .notes
+      .push(Notice { text: "Внимание! Сокращённый день".into(), groups: vec![] });
+    let html = render_snapshot(&snapshot);
+
+    let note = html
+      .find("<p class=MsoNormal>Внимание! Сокращённый день</p>\n")
+      .unwrap();
+    assert!(note < html.find("<table class=MsoTableGrid").unwrap());
+  }
+
+  #[test]
+  fn renders_pages_in_cp1251() {
+    let snapshot = snapshot(vec![lesson("1", None, "Математика", None, None)]);
+    let page = render_page(&snapshot);
+
+    assert_eq!(page.content_type.as_deref(), Some("text/html; charset=windows-1251"));
+    assert_eq!(WINDOWS_1251.decode(&page.bytes).0, render_snapshot(&snapshot));
+  }
+
+  #[test]
+  fn takes_week_parity_from_the_date() {
+    let default = |name: &str, is_even| DefaultLesson {
+      num: Num::Actual("1".into()),
+      name: name.into(),
+      is_even,
+      subgroup: None,
+      teacher: None,
+      classroom: None,
+    };
+    let day = DefaultDay {
+      day: Weekday::Mon,
+      groups: vec![
+        DefaultGroup {
+          name: "Ит1-22".into(),
+          lessons: vec![default("Чётная", Some(true)), default("Нечётная", Some(false)), default("Всегда", None)],
+        },
+        DefaultGroup { name: "Ир3-21".into(), lessons: vec![default("Нечётная", Some(false))] },
+      ],
+    };
+    // 11.09.2023 starts an even week
+    let snapshot = snapshot_from_default(&day, Utc.with_ymd_and_hms(2023, 9, 11, 0, 0, 0).unwrap());
+
+    assert_eq!(snapshot.groups.len(), 1);
+    let lessons = &snapshot.group("Ит1-22").unwrap().lessons;
+    assert_eq!(lessons.iter().map(|l| l.name.as_str()).collect::<Vec<_>>(), ["Чётная", "Всегда"]);
+    assert!(lessons.iter().all(|l| l.source == LessonSource::Page));
+  }
+}
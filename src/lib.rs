@@ -1,18 +1,49 @@
 #[macro_use]
 extern crate lazy_static;
 
-use chrono::Weekday;
-use log::info;
-use maiq_shared::FetchUrl;
+use chrono::{DateTime, Utc, Weekday};
+use log::{info, warn};
+use maiq_shared::lookup::Lookup;
 
 pub use maiq_shared::*;
+pub mod charset;
 pub mod env;
+#[cfg(feature = "remote")]
+pub mod fetch;
+pub mod generator;
 pub mod parser;
+pub mod query;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod source;
+pub mod store;
 
+#[cfg(feature = "remote")]
 pub async fn snapshot_from_remote<T: FetchUrl>(mode: &T) -> anyhow::Result<Snapshot> {
-  let raw = fetch(mode).await?;
-  let table = tl_table_parser::parse_last(&raw).ok_or_else(|| anyhow::anyhow!("Unable to parse table"))?;
-  parser::snapshot::parse_snapshot(table, mode.date())
+  fetch::default_fetcher().snapshot(mode).await
+}
+
+/// First day of the page. Pages listing several dates are parsed in full by [`snapshots_from_html`], here
+/// the rest is dropped with a warning.
+pub fn snapshot_from_html(raw: &str, fallback_date: DateTime<Utc>) -> anyhow::Result<Snapshot> {
+  let mut snapshots = snapshots_from_html(raw, fallback_date)?;
+  if snapshots.len() > 1 {
+    let dropped = snapshots[1..]
+      .iter()
+      .map(|s| s.date.format("%d.%m.%Y").to_string())
+      .collect::<Vec<String>>();
+    warn!(
+      "Page lists {} dates, only {} is taken, dropped {}",
+      snapshots.len(),
+      snapshots[0].date.format("%d.%m.%Y"),
+      dropped.join(", ")
+    );
+  }
+  Ok(snapshots.remove(0))
+}
+
+pub fn snapshots_from_html(raw: &str, fallback_date: DateTime<Utc>) -> anyhow::Result<Vec<Snapshot>> {
+  parser::page::parse_page(raw, fallback_date)
 }
 
 pub fn default_for(weekday: Weekday, group_name: &str) -> Option<&default::DefaultGroup> {
@@ -22,6 +53,28 @@ pub fn default_for(weekday: Weekday, group_name: &str) -> Option<&default::Defau
     .and_then(|d| d.groups.iter().find(|g| g.name == group_name))
 }
 
+pub fn lookup_default(weekday: Weekday, query: &str) -> Lookup<&default::DefaultGroup> {
+  let groups = parser::replace::REPLACEMENTS
+    .iter()
+    .filter(|d| d.day == weekday)
+    .flat_map(|d| d.groups.iter());
+  lookup::lookup(query, groups, |g| g.name.as_str())
+}
+
+/// Resolves a group name typed by a user against the groups of `snapshots` and the configured `GROUPS`,
+/// so a group without replacements on the page is still found.
+pub fn lookup_known_group(query: &str, snapshots: &[&Snapshot]) -> Lookup<String> {
+  let known: Vec<String> = env::groups().into();
+  let mut names = snapshots
+    .iter()
+    .flat_map(|s| s.groups.iter().map(|g| g.name.clone()))
+    .chain(known)
+    .collect::<Vec<String>>();
+  names.sort();
+  names.dedup();
+  lookup::lookup(query, names, |x| x.as_str())
+}
+
 pub fn warmup_defaults() {
   let group_names = parser::replace::REPLACEMENTS
     .iter()
@@ -31,8 +84,7 @@ pub fn warmup_defaults() {
   info!("Loaded replacements for: {:?}", group_names);
 }
 
+#[cfg(feature = "remote")]
 pub async fn fetch<T: FetchUrl>(fetch_mode: &T) -> anyhow::Result<String> {
-  let res = reqwest::get(fetch_mode.url()).await?;
-  let html = res.text_with_charset("windows-1251").await?;
-  Ok(html)
+  fetch::default_fetcher().fetch(fetch_mode).await
 }
@@ -1,14 +1,30 @@
 #[cfg(feature = "cli")]
 mod cli {
+  use chrono::NaiveDate;
   use colored::Colorize;
-  use maiq_parser::{compare::distinct, snapshot_from_remote, warmup_defaults, Fetch, Num};
-  use maiq_shared::{Group, Snapshot};
+  use maiq_parser::{
+    compare::distinct,
+    lookup_known_group,
+    query::{GroupQuery, Scheduled},
+    snapshot_from_remote,
+    store::{self, SnapshotStore},
+    warmup_defaults, Fetch, Num,
+  };
+  use maiq_shared::{
+    bells::BellSchedule,
+    lookup::Lookup,
+    timeline::{self, LessonChange, Version},
+    utils::time,
+    Group, Lesson, LessonSource, Snapshot,
+  };
   use std::{env, fs, io::BufWriter, process::exit};
 
   enum Command {
     Fetch(Fetch),
     Distinct,
     Dump(Fetch),
+    Now,
+    History,
   }
 
   pub async fn run() {
@@ -19,6 +35,8 @@ mod cli {
     let mut args = env::args().skip(1);
     let mut command = None;
     let mut target_group = None;
+    let mut target_subgroup = None;
+    let mut target_date = None;
 
     while let Some(arg) = args.next() {
       match &*arg {
@@ -27,10 +45,23 @@ mod cli {
         "distinct" | "dt" => set_if_none(&mut command, Command::Distinct),
         "dump-today" => set_if_none(&mut command, Command::Dump(Fetch::Today)),
         "dump-next" => set_if_none(&mut command, Command::Dump(Fetch::Next)),
+        "now" => set_if_none(&mut command, Command::Now),
+        "history" => set_if_none(&mut command, Command::History),
         "--group" | "-g" => match args.next() {
           Some(group) => set_if_none(&mut target_group, group),
           None => usage_exit(),
         },
+        "--subgroup" | "-s" => match args.next().and_then(|x| x.parse::<u8>().ok()) {
+          Some(subgroup) => set_if_none(&mut target_subgroup, subgroup),
+          None => usage_exit(),
+        },
+        "--date" | "-d" => match args
+          .next()
+          .and_then(|x| NaiveDate::parse_from_str(&x, "%Y-%m-%d").ok())
+        {
+          Some(date) => set_if_none(&mut target_date, date),
+          None => usage_exit(),
+        },
         "--help" | "-h" => usage_exit(),
         _ => (),
       }
@@ -45,13 +76,21 @@ mod cli {
     match command.unwrap() {
       Command::Fetch(ref fetch) => match snapshot_from_remote(fetch).await {
         Ok(snapshot) => match target_group {
-          Some(g) => display_group(snapshot, &g),
+          Some(g) => display_group(snapshot, &g, target_subgroup),
           None => print_snapshot(&snapshot),
         },
         Err(x) => eprintln!("error -> {}", x),
       },
       Command::Distinct => show_distinct().await,
-      Command::Dump(ref fetch) => dump(fetch).await,
+      Command::Dump(ref fetch) => dump(fetch, target_group.as_deref(), target_subgroup).await,
+      Command::Now => match target_group {
+        Some(g) => show_now(&g, target_subgroup).await,
+        None => usage_exit(),
+      },
+      Command::History => match target_group {
+        Some(g) => show_history(&g, target_date.unwrap_or_else(|| time::now_date().date_naive())),
+        None => usage_exit(),
+      },
     }
   }
 
@@ -69,8 +108,12 @@ mod cli {
       today (t) | next (n)
       distinct (dt)
       dump-today | dump-next
+      now -g <name> - текущая и следующая пара группы
+      history -g <name> [-d <date>] - как менялись замены группы за день
     options:
       --group (-g) <name> - вывести только указанную группу
+      --subgroup (-s) <num> - оставить только пары указанной подгруппы
+      --date (-d) <ГГГГ-ММ-ДД> - дата для history, по умолчанию сегодня
       --help (-h) - это сообщение"#
     );
     exit(0);
@@ -84,21 +127,147 @@ mod cli {
     }
   }
 
-  async fn dump(fetch: &Fetch) {
+  async fn show_now(group_name: &str, subgroup: Option<u8>) {
+    let today = snapshot_from_remote(&Fetch::Today).await.ok();
+    let next = snapshot_from_remote(&Fetch::Next).await.ok();
+    let group_name = match resolve_group(group_name, &[today.as_ref(), next.as_ref()]) {
+      Some(x) => x,
+      None => return,
+    };
+    let bells = load_bells();
+    let query = GroupQuery::new(&group_name)
+      .subgroup(subgroup)
+      .snapshot(today.as_ref())
+      .snapshot(next.as_ref())
+      .bells(bells.as_ref());
+    let now = time::now();
+
+    println!("Группа {}\n", group_name.bright_white());
+    match query.is_free(now) {
+      true => println!("Сегодня пар нет"),
+      false => println!("Сегодня пар: {}", query.count(now)),
+    }
+    print_scheduled("Первая", query.first(now));
+    print_scheduled("Последняя", query.last(now));
+    print_scheduled("Сейчас", query.current(now));
+    print_scheduled("Следующая", query.next(now));
+  }
+
+  /// Versions are read from `SNAPSHOTS_DIR`, or from the current directory where `dump-today` / `dump-next` write.
+  fn show_history(group_name: &str, date: NaiveDate) {
+    let dir = Option::<String>::from(maiq_parser::env::snapshots_dir()).unwrap_or_else(|| ".".into());
+    let snapshots = match SnapshotStore::open(dir).versions(date) {
+      Ok(x) => x,
+      Err(e) => return eprintln!("error -> {}", e),
+    };
+    let group_name = match resolve_group(group_name, &snapshots.iter().map(Some).collect::<Vec<_>>()) {
+      Some(x) => x,
+      None => return,
+    };
+    let versions = timeline::timeline(&snapshots, date, &group_name);
+
+    println!("Группа {} на {}: версий {}\n", group_name.bright_white(), date.format("%d.%m.%Y"), versions.len());
+    versions.iter().for_each(print_version);
+  }
+
+  fn print_version(version: &Version) {
+    println!(
+      "{} {} ({})",
+      version.parsed_date.format("%d.%m %H:%M").to_string().bright_white(),
+      version.uid.as_deref().unwrap_or("-").purple(),
+      version.snapshot_uid
+    );
+    if version.uid.is_none() {
+      println!("\t{}", "Группы нет в заменах".dimmed());
+    }
+
+    for change in version.changes.iter() {
+      match change {
+        LessonChange::Added(lesson) => {
+          print!("\t{} ", "+".green());
+          print_lesson(lesson);
+        }
+        LessonChange::Removed(lesson) => {
+          print!("\t{} ", "-".red());
+          print_lesson(lesson);
+        }
+        LessonChange::Changed { before, after } => {
+          print!("\t{} ", "~".yellow());
+          print_lesson(before);
+          print!("\t{} ", "→".yellow());
+          print_lesson(after);
+        }
+      }
+    }
+    println!()
+  }
+
+  fn load_bells() -> Option<BellSchedule> {
+    let path = maiq_parser::env::var("BELLS")?;
+    let file = fs::File::open(path).ok()?;
+    serde_json::from_reader(file).ok()
+  }
+
+  fn print_scheduled(title: &str, scheduled: Option<Scheduled>) {
+    let scheduled = match scheduled {
+      Some(x) => x,
+      None => return,
+    };
+
+    print!("{}: {} ", title, scheduled.date.format("%d.%m"));
+    if let Some(bell) = scheduled.bell {
+      print!("{} ", format!("{}-{}", bell.start.format("%H:%M"), bell.end.format("%H:%M")).purple());
+    }
+    print_lesson(&scheduled.lesson);
+  }
+
+  async fn dump(fetch: &Fetch, group_name: Option<&str>, subgroup: Option<u8>) {
     let snapshot = snapshot_from_remote(fetch).await;
     let snapshot = snapshot.unwrap();
-    let file_name = format!("{}_{}.json", snapshot.date.format("%d-%m-%Y"), snapshot.uid);
-    let file = fs::File::create(file_name).expect("unable to open file");
+    let group_name = match group_name {
+      Some(name) => match resolve_group(name, &[Some(&snapshot)]) {
+        Some(x) => Some(x),
+        None => return,
+      },
+      None => None,
+    };
+    let file = fs::File::create(store::file_name(&snapshot)).expect("unable to open file");
     let writer = BufWriter::new(file);
-    serde_json::to_writer_pretty(writer, &snapshot).expect("unable to write file");
+    match group_name {
+      Some(name) => serde_json::to_writer_pretty(writer, &snapshot.tiny_personal(&name, subgroup)),
+      None => serde_json::to_writer_pretty(writer, &snapshot),
+    }
+    .expect("unable to write file");
   }
 
-  fn display_group(snapshot: Snapshot, group_name: &str) {
+  fn resolve_group(query: &str, snapshots: &[Option<&Snapshot>]) -> Option<String> {
+    let snapshots = snapshots.iter().flatten().copied().collect::<Vec<&Snapshot>>();
+    match lookup_known_group(query, &snapshots) {
+      Lookup::Found(name) => Some(name),
+      Lookup::Missing(suggestions) => {
+        print_missing(query, &suggestions);
+        None
+      }
+    }
+  }
+
+  fn print_missing(group_name: &str, suggestions: &[String]) {
+    println!("Нет группы {}", group_name);
+    if !suggestions.is_empty() {
+      println!("Возможно, имелось в виду: {}", suggestions.join(", ").bright_white());
+    }
+  }
+
+  fn display_group(snapshot: Snapshot, group_name: &str, subgroup: Option<u8>) {
     println!("{} от {}\n", snapshot.uid, snapshot.date);
-    let group = snapshot.group(group_name);
-    match group {
-      Some(g) => print_group(g),
-      None => println!("Нет группы {}", group_name),
+    match snapshot.lookup_group(group_name) {
+      Lookup::Found(g) => {
+        print_group(&g.personal(subgroup));
+        for note in snapshot.notes_for(&g.name) {
+          println!("{} {}", "!".yellow(), note.text);
+        }
+      }
+      Lookup::Missing(suggestions) => print_missing(group_name, &suggestions),
     }
   }
 
@@ -108,29 +277,65 @@ mod cli {
       print_group(group);
       println!()
     }
+
+    if !s.kind.is_regular() {
+      println!("{}", s.kind.to_string().bright_yellow());
+    }
+
+    for note in &s.notes {
+      println!("{} {}", "!".yellow(), note.text);
+    }
+
+    if !s.discovered_groups.is_empty() {
+      println!("{} {}", "Новые группы:".yellow(), s.discovered_groups.join(", "));
+    }
   }
 
   fn print_group(g: &Group) {
     println!("Группа {} ({}) ({})", g.name.bright_white(), g.uid.purple(), g.lessons.len());
     for lesson in &g.lessons {
       print!("\t");
-      if let Num::Actual(ref num) = lesson.num {
-        print!("{} ", format!("#{}", num).bright_white());
-      }
-      if let Some(sub) = lesson.subgroup {
-        print!("{} ", format!("(п. {sub})").green())
-      }
-      print!("{} ", lesson.name);
+      print_lesson(lesson);
+    }
+  }
 
-      if let Some(classroom) = lesson.classroom.as_ref() {
-        print!("в {}", classroom.green());
-      }
+  fn print_lesson(lesson: &Lesson) {
+    if let Num::Actual(ref num) = lesson.num {
+      print!("{} ", format!("#{}", num).bright_white());
+    }
+    if let Some(sub) = lesson.subgroup {
+      print!("{} ", format!("(п. {sub})").green())
+    }
+    if lesson.is_cancelled() {
+      let name = lesson.replaces.as_ref().map(|l| l.display_name()).unwrap_or("пара");
+      println!("{} {}", "Отменена:".red(), name);
+      return;
+    }
+    print!("{} ", lesson.display_name());
+    if !lesson.kind.is_regular() {
+      print!("{} ", format!("[{}]", lesson.kind).yellow());
+    }
+    if lesson.is_remote {
+      print!("{} ", "[дистанционно]".cyan());
+    }
 
-      if let Some(teacher) = lesson.teacher.as_ref() {
-        print!(". Преподаватель: {}", teacher.green())
-      }
-      println!()
+    if let Some(classroom) = lesson.classroom.as_ref() {
+      print!("в {}", classroom.green());
+    }
+
+    if let Some(teacher) = lesson.teacher.as_ref() {
+      print!(". Преподаватель: {}", teacher.green())
+    }
+
+    match lesson.source {
+      LessonSource::Page => (),
+      LessonSource::Default => print!(" {}", "[по расписанию]".dimmed()),
+      LessonSource::DefaultWithPageClassroom => print!(" {}", "[по расписанию, аудитория из замен]".dimmed()),
+    }
+    if !lesson.source.is_page() && !lesson.raw.is_empty() {
+      print!(" {}", format!("<{}>", lesson.raw.join(" | ")).dimmed());
     }
+    println!()
   }
 }
 
@@ -1,12 +1,16 @@
 use chrono::{DateTime, Datelike, Utc};
 use maiq_shared::utils::time::now_date;
 
-const MONTHS: [&str; 12] =
+pub(crate) const MONTHS: [&str; 12] =
   ["января", "февраля", "марта", "апреля", "мая", "июня", "июля", "августа", "сентября", "октября", "ноября", "декабря"];
 
 pub fn parse_date<T: Iterator<Item = Vec<String>>>(row: &mut T) -> Option<DateTime<Utc>> {
-  let x = row.next().unwrap();
-  let mut split = x.first().unwrap().split(' ');
+  let x = row.next()?;
+  parse_date_text(x.first()?)
+}
+
+pub fn parse_date_text(text: &str) -> Option<DateTime<Utc>> {
+  let mut split = text.split(' ');
 
   while let Some(word) = split.next() {
     let day = word.trim().parse::<u32>();
@@ -26,7 +30,7 @@ pub fn parse_date<T: Iterator<Item = Vec<String>>>(row: &mut T) -> Option<DateTi
       None => continue,
     };
 
-    return now_date().with_day(day).unwrap().with_month(month);
+    return now_date().with_day(1)?.with_month(month)?.with_day(day);
   }
 
   None
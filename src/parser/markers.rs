@@ -0,0 +1,117 @@
+use maiq_shared::LessonKind;
+
+const REMOTE_WORDS: [&str; 3] = ["дистанционно", "онлайн", "online"];
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Markers {
+  pub kind: LessonKind,
+  pub is_remote: bool,
+  pub clean_name: Option<String>,
+}
+
+/// Looks for kind and format markers in a lesson name. Markers in parentheses (`(лаб.)`, `(пр.)`, `(дист.)`)
+/// and standalone remote words are cut out of the clean name, exams and consultations are only recognised
+/// since the word is usually the name itself.
+pub fn parse_markers(name: &str) -> Markers {
+  let mut markers = Markers::default();
+  let mut clean = String::with_capacity(name.len());
+  let mut rest = name;
+
+  while let Some((head, tail)) = rest.split_once('(') {
+    let (tag, tail) = match tail.split_once(')') {
+      Some(x) => x,
+      None => break,
+    };
+
+    clean.push_str(head);
+    match tag_kind(tag) {
+      Some(Tag::Kind(kind)) => markers.kind = kind,
+      Some(Tag::Remote) => markers.is_remote = true,
+      None => {
+        clean.push('(');
+        clean.push_str(tag);
+        clean.push(')');
+      }
+    }
+    rest = tail;
+  }
+  clean.push_str(rest);
+
+  let words = clean
+    .split_whitespace()
+    .filter(|word| {
+      let is_remote = REMOTE_WORDS.contains(&normalize(word).as_str());
+      markers.is_remote |= is_remote;
+      !is_remote
+    })
+    .collect::<Vec<&str>>();
+  let clean = words.join(" ").trim_end_matches([',', '-', ':', ' ']).to_string();
+
+  if markers.kind.is_regular() {
+    let lower = normalize(&clean);
+    if lower.contains("экзамен") {
+      markers.kind = LessonKind::Exam;
+    } else if lower.contains("консультац") {
+      markers.kind = LessonKind::Consultation;
+    }
+  }
+
+  if clean != name.trim() && !clean.is_empty() {
+    markers.clean_name = Some(clean);
+  }
+  markers
+}
+
+enum Tag {
+  Kind(LessonKind),
+  Remote,
+}
+
+fn tag_kind(tag: &str) -> Option<Tag> {
+  let tag = normalize(tag);
+  let word = tag.split([' ', '.']).next().unwrap_or_default();
+  let kind = match word {
+    "л/р" | "лр" => LessonKind::Lab,
+    "п/з" | "пз" | "пр" => LessonKind::Practice,
+    "лк" => LessonKind::Lecture,
+    "дот" | "онлайн" | "online" => return Some(Tag::Remote),
+    w if w.starts_with("лаб") => LessonKind::Lab,
+    w if w.starts_with("практ") => LessonKind::Practice,
+    w if w.starts_with("лек") => LessonKind::Lecture,
+    w if w.starts_with("экз") => LessonKind::Exam,
+    w if w.starts_with("конс") => LessonKind::Consultation,
+    w if w.starts_with("дист") => return Some(Tag::Remote),
+    _ => return None,
+  };
+  Some(Tag::Kind(kind))
+}
+
+fn normalize(raw: &str) -> String {
+  raw
+    .trim_matches(|c: char| c.is_whitespace() || matches!(c, ',' | ';'))
+    .to_lowercase()
+    .replace('ё', "е")
+}
+
+#[cfg(test)]
+mod tests {
+  use maiq_shared::LessonKind;
+
+  use super::parse_markers;
+
+  #[test]
+  fn cuts_markers_out_of_name() {
+    let lab = parse_markers("Информационные технологии (лаб.)");
+    assert_eq!(lab.kind, LessonKind::Lab);
+    assert_eq!(lab.clean_name.as_deref(), Some("Информационные технологии"));
+
+    let remote = parse_markers("Математика (пр.) дистанционно");
+    assert_eq!(remote.kind, LessonKind::Practice);
+    assert!(remote.is_remote);
+    assert_eq!(remote.clean_name.as_deref(), Some("Математика"));
+
+    let exam = parse_markers("Экзамен: Физика (1 п/г)");
+    assert_eq!(exam.kind, LessonKind::Exam);
+    assert_eq!(exam.clean_name, None);
+  }
+}
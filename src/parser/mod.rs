@@ -1,4 +1,6 @@
-mod date;
+pub(crate) mod date;
+pub mod markers;
+pub mod page;
 pub(crate) mod replace;
 pub mod snapshot;
 
@@ -0,0 +1,436 @@
+use chrono::{DateTime, Utc};
+use maiq_shared::{DayKind, Notice, Snapshot, Uid};
+use tl::{Node, NodeHandle, Parser};
+
+use super::{
+  date,
+  snapshot::{is_group_name_like, parse_tables},
+  Rows,
+};
+use crate::env;
+
+const MIN_LESSON_CELLS: usize = 3;
+const SKIP_TAGS: [&str; 5] = ["table", "script", "style", "head", "title"];
+const BLOCK_TAGS: [&str; 14] = ["p", "div", "br", "li", "ul", "ol", "center", "h1", "h2", "h3", "h4", "h5", "h6", "body"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableKind {
+  Date(DateTime<Utc>),
+  Lessons(Option<DateTime<Utc>>),
+  Notes,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Page {
+  pub tables: Vec<Rows>,
+  pub text: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Day {
+  pub date: Option<DateTime<Utc>>,
+  pub tables: Vec<Rows>,
+  pub notes: Vec<String>,
+}
+
+pub fn parse_page(html: &str, fallback_date: DateTime<Utc>) -> anyhow::Result<Vec<Snapshot>> {
+  let page = extract_page(html)?;
+  let mut days = split_days(page.tables);
+  if days.is_empty() && page.text.iter().any(|x| day_marker(x).is_some()) {
+    days.push(Day::default());
+  }
+  let first = days
+    .first_mut()
+    .ok_or_else(|| anyhow::anyhow!("Unable to parse table"))?;
+  let mut notes = page
+    .text
+    .into_iter()
+    .filter(|x| is_notice(x))
+    .collect::<Vec<String>>();
+  notes.append(&mut first.notes);
+  first.notes = notes;
+
+  days
+    .into_iter()
+    .map(|day| {
+      let mut snapshot = parse_tables(day.tables, day.date.unwrap_or(fallback_date))?;
+      snapshot.notes = day.notes.into_iter().map(notice).collect();
+      snapshot.kind = day_kind(&snapshot.notes);
+      snapshot.refresh();
+      Ok(snapshot)
+    })
+    .collect()
+}
+
+pub fn extract_tables(html: &str) -> anyhow::Result<Vec<Rows>> {
+  Ok(extract_page(html)?.tables)
+}
+
+pub fn extract_page(html: &str) -> anyhow::Result<Page> {
+  let dom = tl::parse(html, tl::ParserOptions::default())?;
+  let parser = dom.parser();
+  let cells = |tr: &tl::HTMLTag| {
+    tr.children()
+      .top()
+      .iter()
+      .filter_map(|x| x.get(parser).and_then(|n| n.as_tag()))
+      .filter(|x| matches!(&*x.name().as_utf8_str(), "td" | "th"))
+      .map(|x| clean(&x.inner_text(parser)))
+      .collect::<Vec<String>>()
+  };
+
+  let tag = |handle: &NodeHandle| handle.get(parser).and_then(|n| n.as_tag());
+  let tables = dom
+    .query_selector("table")
+    .into_iter()
+    .flatten()
+    .collect::<Vec<NodeHandle>>();
+  // Nested tables are a part of the cell they sit in, their rows must not be read twice
+  let nested = tables
+    .iter()
+    .filter_map(tag)
+    .flat_map(|table| table.query_selector(parser, "table").into_iter().flatten())
+    .collect::<Vec<NodeHandle>>();
+
+  let tables = tables
+    .iter()
+    .filter(|handle| !nested.contains(handle))
+    .filter_map(tag)
+    .map(|table| {
+      rows(table, parser)
+        .into_iter()
+        .map(cells)
+        .filter(|row| !row.is_empty())
+        .collect::<Rows>()
+    })
+    .filter(|rows| !rows.is_empty())
+    .collect();
+
+  let mut text = vec![];
+  let mut line = String::new();
+  for handle in dom.children() {
+    collect_text(*handle, parser, &mut text, &mut line);
+  }
+  flush(&mut line, &mut text);
+
+  Ok(Page { tables, text })
+}
+
+/// Rows of `table` itself, directly or through `thead` / `tbody` / `tfoot`.
+fn rows<'a>(table: &'a tl::HTMLTag<'a>, parser: &'a Parser<'a>) -> Vec<&'a tl::HTMLTag<'a>> {
+  let mut rows = vec![];
+  for child in table
+    .children()
+    .top()
+    .iter()
+    .filter_map(|x| x.get(parser).and_then(|n| n.as_tag()))
+  {
+    match &*child.name().as_utf8_str().to_lowercase() {
+      "tr" => rows.push(child),
+      "thead" | "tbody" | "tfoot" => rows.extend(rows_of_section(child, parser)),
+      _ => (),
+    }
+  }
+  rows
+}
+
+fn rows_of_section<'a>(section: &'a tl::HTMLTag<'a>, parser: &'a Parser<'a>) -> Vec<&'a tl::HTMLTag<'a>> {
+  section
+    .children()
+    .top()
+    .iter()
+    .filter_map(|x| x.get(parser).and_then(|n| n.as_tag()))
+    .filter(|x| x.name().as_utf8_str().eq_ignore_ascii_case("tr"))
+    .collect()
+}
+
+pub fn classify(rows: &Rows) -> TableKind {
+  let date = rows.first().and_then(|row| date::parse_date_text(&row.join(" ")));
+  // A date header may be split into as many cells as a lesson row, any other first row is checked too
+  let has_lessons = rows
+    .iter()
+    .skip(usize::from(date.is_some()))
+    .any(|row| row.len() >= MIN_LESSON_CELLS);
+  match (date, has_lessons) {
+    (date, true) => TableKind::Lessons(date),
+    (Some(date), false) => TableKind::Date(date),
+    (None, false) => TableKind::Notes,
+  }
+}
+
+/// Groups lesson tables by the date header preceding them. Tables under the same date
+/// (e.g. separate sections per building) end up in a single day.
+pub fn split_days(tables: Vec<Rows>) -> Vec<Day> {
+  let mut days: Vec<Day> = vec![];
+  let mut current = Day::default();
+
+  for mut rows in tables {
+    let kind = classify(&rows);
+    let date = match kind {
+      TableKind::Notes => {
+        current
+          .notes
+          .extend(rows.iter().map(|row| row.join(" ")).filter(|x| is_notice(x)));
+        continue;
+      }
+      TableKind::Date(date) => Some(date),
+      TableKind::Lessons(date) => date,
+    };
+
+    if let Some(date) = date.filter(|d| current.date != Some(*d)) {
+      match current.date.is_none() && current.tables.is_empty() {
+        true => current.date = Some(date),
+        false => days.push(std::mem::replace(&mut current, Day { date: Some(date), ..Default::default() })),
+      }
+    }
+
+    if let TableKind::Lessons(date) = kind {
+      if date.is_some() {
+        rows.remove(0);
+      }
+      let (notes, lessons): (Rows, Rows) = rows.into_iter().partition(|row| is_notice_row(row));
+      current.notes.extend(notes.into_iter().map(|mut row| row.remove(0)));
+      current.tables.push(lessons);
+    }
+  }
+
+  days.push(current);
+  days.retain(|d| !d.tables.is_empty() || d.date.is_some());
+  days
+}
+
+fn is_notice_row(row: &[String]) -> bool {
+  match row {
+    [cell] => is_notice(cell) && !is_group_name_like(cell.split(' ').next().unwrap_or_default()),
+    _ => false,
+  }
+}
+
+/// A line of text worth showing: a few words that are not a date header or a bare group name. A single
+/// word only makes it as a day marker.
+fn is_notice(text: &str) -> bool {
+  let words = text
+    .split_whitespace()
+    .filter(|w| w.chars().any(char::is_alphabetic))
+    .collect::<Vec<&str>>();
+  text.chars().filter(|c| c.is_alphabetic()).count() > 3
+    && date::parse_date_text(text).is_none()
+    && !words.iter().all(|w| is_group_name_like(w))
+    && (words.len() > 1 || day_marker(text).is_some())
+}
+
+fn notice(text: String) -> Notice {
+  let known: Vec<String> = env::groups().into();
+  let mut groups = text
+    .split(|c: char| c.is_whitespace() || matches!(c, ',' | ';' | ':' | '(' | ')'))
+    .filter(|word| is_group_name_like(word) || known.iter().any(|g| g == word))
+    .map(|word| word.to_string())
+    .collect::<Vec<String>>();
+  groups.sort();
+  groups.dedup();
+  Notice { text, groups }
+}
+
+/// Only notices addressed to everyone may change the kind of the whole day.
+pub fn day_kind(notes: &[Notice]) -> DayKind {
+  notes
+    .iter()
+    .filter(|n| n.groups.is_empty())
+    .find_map(|n| day_marker(&n.text))
+    .unwrap_or_default()
+}
+
+/// The whole line has to be a day header like "Сокращённый день" or "Занятия отменены": every word is
+/// either a marker or a filler word, a marker inside an unrelated notice doesn't count.
+fn day_marker(text: &str) -> Option<DayKind> {
+  const MARKERS: [(&str, DayKind); 7] = [
+    ("сокращ", DayKind::Shortened),
+    ("самоподготов", DayKind::SelfStudy),
+    ("практик", DayKind::Practice),
+    ("выходн", DayKind::Holiday),
+    ("праздни", DayKind::Holiday),
+    ("нерабоч", DayKind::Holiday),
+    ("отмен", DayKind::Holiday),
+  ];
+  const FILLERS: [&str; 14] = [
+    "внимание",
+    "сегодня",
+    "завтра",
+    "день",
+    "дни",
+    "для",
+    "всех",
+    "групп",
+    "занятия",
+    "занятий",
+    "учебная",
+    "учебной",
+    "производственная",
+    "производственной",
+  ];
+
+  let text = text.to_lowercase().replace('ё', "е");
+  let words = text
+    .split(|c: char| !c.is_alphanumeric() && c != '-')
+    .filter(|w| !w.is_empty())
+    .collect::<Vec<&str>>();
+  let marker = |word: &str| {
+    MARKERS
+      .iter()
+      .find(|(marker, _)| word.starts_with(marker))
+      .map(|(_, kind)| *kind)
+  };
+
+  match words.iter().all(|w| marker(w).is_some() || FILLERS.contains(w)) {
+    true => words.iter().find_map(|w| marker(w)),
+    false => None,
+  }
+}
+
+fn collect_text(handle: NodeHandle, parser: &Parser, lines: &mut Vec<String>, line: &mut String) {
+  match handle.get(parser) {
+    Some(Node::Raw(raw)) => {
+      line.push_str(&raw.as_utf8_str());
+      line.push(' ');
+    }
+    Some(Node::Tag(tag)) => {
+      let name = tag.name().as_utf8_str().to_lowercase();
+      if SKIP_TAGS.contains(&name.as_str()) {
+        flush(line, lines);
+        return;
+      }
+
+      let is_block = BLOCK_TAGS.contains(&name.as_str());
+      if is_block {
+        flush(line, lines);
+      }
+      for child in tag.children().top().iter() {
+        collect_text(*child, parser, lines, line);
+      }
+      if is_block {
+        flush(line, lines);
+      }
+    }
+    _ => (),
+  }
+}
+
+fn flush(line: &mut String, lines: &mut Vec<String>) {
+  let text = clean(line);
+  if !text.is_empty() {
+    lines.push(text);
+  }
+  line.clear();
+}
+
+fn clean(raw: &str) -> String {
+  raw
+    .replace("&nbsp;", " ")
+    .split_whitespace()
+    .collect::<Vec<&str>>()
+    .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+  use maiq_shared::DayKind;
+
+  use super::{classify, day_kind, day_marker, extract_page, is_notice, notice, parse_page, split_days, TableKind};
+
+  fn row(cells: &[&str]) -> Vec<String> {
+    cells.iter().map(|x| x.to_string()).collect()
+  }
+
+  #[test]
+  fn extracts_every_table() {
+    let html = "<table><tr><td>Изменения на 12 сентября</td></tr></table>\
+      <p>Классный час в 13:00</p>\
+      <table><tr><th>Ит1-22</th><td>1</td><td>Математика,&nbsp;Иванов</td><td> 305 </td></tr></table>";
+    let page = extract_page(html).unwrap();
+    assert_eq!(page.tables.len(), 2);
+    assert_eq!(page.tables[1][0], row(&["Ит1-22", "1", "Математика, Иванов", "305"]));
+    assert_eq!(page.text, vec!["Классный час в 13:00".to_string()]);
+  }
+
+  #[test]
+  fn reads_nested_tables_once() {
+    let html = "<table><tbody>\
+      <tr><td>Ит1-22</td><td>1</td><td><table><tr><td>Математика</td></tr></table></td><td>305</td></tr>\
+      </tbody><tr><td>Ит1-22</td><td>2</td><td>Физика</td><td>306</td></tr></table>";
+    let page = extract_page(html).unwrap();
+    assert_eq!(page.tables.len(), 1);
+    assert_eq!(page.tables[0], vec![row(&["Ит1-22", "1", "Математика", "305"]), row(&["Ит1-22", "2", "Физика", "306"])]);
+  }
+
+  #[test]
+  fn classifies_tables_without_a_header() {
+    assert!(matches!(classify(&vec![row(&["Ит1-22", "1", "Математика", "305"])]), TableKind::Lessons(None)));
+    assert!(matches!(classify(&vec![row(&["Изменения на 12 сентября", "", "", ""])]), TableKind::Date(_)));
+    assert!(matches!(classify(&vec![row(&["Объявление для всех"])]), TableKind::Notes));
+  }
+
+  #[test]
+  fn splits_tables_by_date() {
+    let lessons = vec![row(&["Группа", "Пара", "Дисциплина", "Ауд."]), row(&["Ит1-22", "1", "Математика", "305"])];
+    let tables = vec![
+      vec![row(&["Изменения на 12 сентября"])],
+      lessons.clone(),
+      vec![row(&["Объявление для всех"])],
+      [lessons.clone(), vec![row(&["Сокращённый день для Ит1-22"])]].concat(),
+      [vec![row(&["Изменения на 13 сентября"])], lessons].concat(),
+    ];
+
+    let days = split_days(tables);
+    assert_eq!(days.len(), 2);
+    assert_eq!(days[0].tables.len(), 2);
+    assert_eq!(days[0].notes, vec!["Объявление для всех".to_string(), "Сокращённый день для Ит1-22".to_string()]);
+    assert_eq!(days[1].tables.len(), 1);
+    assert_eq!(days[1].tables[0][0][0], "Группа");
+  }
+
+  #[test]
+  fn scopes_notices_to_groups() {
+    let notice = notice("Классный час для групп Ит1-22, Ир3-21 (Ит1-22 в 305)".into());
+    assert_eq!(notice.groups, vec!["Ир3-21".to_string(), "Ит1-22".to_string()]);
+  }
+
+  #[test]
+  fn skips_noise() {
+    assert!(is_notice("Классный час в 13:00"));
+    assert!(is_notice("Самоподготовка"));
+    assert!(!is_notice("Внимание"));
+    assert!(!is_notice("Ит1-22 Ир3-21"));
+    assert!(!is_notice("Изменения в расписании на 12 сентября"));
+  }
+
+  #[test]
+  fn detects_day_kind() {
+    let date = maiq_shared::utils::time::now_date();
+    let holiday = parse_page("<table><tr><td>Изменения на 12 сентября</td></tr></table><p>Праздничный день</p>", date).unwrap();
+    assert_eq!(holiday.len(), 1);
+    assert_eq!(holiday[0].kind, DayKind::Holiday);
+    assert!(holiday[0].groups.is_empty());
+
+    let shortened = parse_page(
+      "<p>Сокращённый день для Ит1-22</p><table><tr><td>Группа</td><td>Пара</td><td>Дисциплина</td></tr>\
+      <tr><td>Ит1-22</td><td>1</td><td>Математика</td></tr></table>",
+      date,
+    )
+    .unwrap();
+    assert_eq!(shortened[0].kind, DayKind::Regular);
+    assert_eq!(parse_page("<p>Самоподготовка</p>", date).unwrap()[0].kind, DayKind::SelfStudy);
+  }
+
+  #[test]
+  fn day_markers_take_the_whole_line() {
+    assert_eq!(day_marker("Сокращённый день!"), Some(DayKind::Shortened));
+    assert_eq!(day_marker("День самоподготовки"), Some(DayKind::SelfStudy));
+    assert_eq!(day_marker("Внимание: занятия отменены"), Some(DayKind::Holiday));
+    assert_eq!(day_marker("Учебная практика"), Some(DayKind::Practice));
+
+    assert_eq!(day_marker("Собрание по практике в 13:00 в актовом зале"), None);
+    assert_eq!(day_marker("Физика отменена"), None);
+    assert_eq!(day_marker("Сдать отчёты до праздников"), None);
+    assert_eq!(day_kind(&[notice("Консультация по производственной практике в 305".into())]), DayKind::Regular);
+  }
+}
@@ -1,6 +1,12 @@
 use chrono::{DateTime, Datelike, Utc};
 use include_dir::{include_dir, Dir};
-use maiq_shared::{default::DefaultDay, utils::time, Group, Lesson};
+use maiq_shared::{
+  default::{DefaultDay, DefaultLesson},
+  utils::time,
+  Group, Lesson, LessonSource,
+};
+
+use super::markers::parse_markers;
 
 lazy_static! {
   pub static ref REPLACEMENTS: Vec<DefaultDay> = load_defaults();
@@ -9,41 +15,84 @@ lazy_static! {
 static DEFAULT_JSON_DIR: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/default/");
 
 pub fn replace_all_default(groups: &mut [Group], date: DateTime<Utc>) {
+  replace_all_default_with(&REPLACEMENTS, groups, date)
+}
+
+/// Same as [`replace_all_default`], but against the given default timetable instead of the bundled one.
+pub fn replace_all_default_with(defaults: &[DefaultDay], groups: &mut [Group], date: DateTime<Utc>) {
   groups.iter_mut().for_each(|g| {
-    g.lessons
-      .iter_mut()
-      .for_each(|l| try_replace_if_need(&g.name, l, date))
+    g.lessons.iter_mut().for_each(|l| match l.is_cancelled() {
+      true => l.replaces = find_default(defaults, &g.name, l, date).map(|d| Box::new(from_default(d))),
+      false => try_replace_if_need(defaults, &g.name, l, date),
+    })
   });
 }
 
-pub fn try_replace_if_need(group_name: &str, lesson: &mut Lesson, date: DateTime<Utc>) {
+pub fn try_replace_if_need(defaults: &[DefaultDay], group_name: &str, lesson: &mut Lesson, date: DateTime<Utc>) {
   if matches!(lesson.name.as_str(), "По расписанию" | "по расписанию") {
-    try_replace(lesson, group_name, date)
+    try_replace(defaults, lesson, group_name, date)
   }
 }
 
-pub fn try_replace(lesson: &mut Lesson, group_name: &str, date: DateTime<Utc>) {
+pub fn try_replace(defaults: &[DefaultDay], lesson: &mut Lesson, group_name: &str, date: DateTime<Utc>) {
+  if let Some(default) = find_default(defaults, group_name, lesson, date) {
+    let (classroom, source) = match lesson.classroom.take() {
+      Some(classroom) => (Some(classroom), LessonSource::DefaultWithPageClassroom),
+      None => (default.classroom.clone(), LessonSource::Default),
+    };
+    let raw = std::mem::take(&mut lesson.raw);
+    *lesson = Lesson { classroom, source, raw, ..from_default(default) };
+  }
+}
+
+pub fn default_lessons_with(defaults: &[DefaultDay], group_name: &str, date: DateTime<Utc>) -> Vec<Lesson> {
+  let weekday = date.weekday();
+  let is_even = time::is_week_even(&date);
+  defaults
+    .iter()
+    .find(|d| d.day == weekday)
+    .and_then(|d| d.groups.iter().find(|g| g.name.as_str() == group_name))
+    .map(|g| {
+      g.lessons
+        .iter()
+        .filter(|l| l.is_even.map(|e| e == is_even).unwrap_or(true))
+        .map(from_default)
+        .collect()
+    })
+    .unwrap_or_default()
+}
+
+fn find_default<'a>(
+  defaults: &'a [DefaultDay],
+  group_name: &str,
+  lesson: &Lesson,
+  date: DateTime<Utc>,
+) -> Option<&'a DefaultLesson> {
   let weekday = date.weekday();
   let is_even = time::is_week_even(&date);
-  if let Some(default) = REPLACEMENTS.iter().find(|d| d.day == weekday).and_then(|d| {
+  defaults.iter().find(|d| d.day == weekday).and_then(|d| {
     d.groups.iter().find(|g| g.name.as_str() == group_name).and_then(|g| {
       g.lessons.iter().find(|l| match l.is_even {
         Some(e) => l.num == lesson.num && e == is_even,
         None => l.num == lesson.num,
       })
     })
-  }) {
-    *lesson = Lesson {
-      num: lesson.num.clone(),
-      name: default.name.clone(),
-      subgroup: default.subgroup,
-      teacher: default.teacher.clone(),
-      classroom: lesson.classroom.clone(),
-    };
+  })
+}
 
-    if lesson.classroom.is_none() {
-      lesson.classroom = default.classroom.clone();
-    }
+pub(crate) fn from_default(lesson: &DefaultLesson) -> Lesson {
+  let markers = parse_markers(&lesson.name);
+  Lesson {
+    num: lesson.num.clone(),
+    name: lesson.name.clone(),
+    subgroup: lesson.subgroup,
+    teacher: lesson.teacher.clone(),
+    classroom: lesson.classroom.clone(),
+    kind: markers.kind,
+    is_remote: markers.is_remote,
+    clean_name: markers.clean_name,
+    source: LessonSource::Default,
+    ..Default::default()
   }
 }
 
@@ -57,3 +106,86 @@ fn load_defaults() -> Vec<DefaultDay> {
     })
     .collect::<Vec<DefaultDay>>()
 }
+
+#[cfg(test)]
+mod tests {
+  use chrono::{DateTime, TimeZone, Utc, Weekday};
+  use maiq_shared::{
+    default::{DefaultDay, DefaultGroup, DefaultLesson},
+    Group, Lesson, LessonSource, LessonStatus, Num,
+  };
+
+  use super::replace_all_default_with;
+
+  fn monday() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2023, 9, 11, 0, 0, 0).unwrap()
+  }
+
+  fn defaults() -> Vec<DefaultDay> {
+    let lesson = |num: &str, name: &str, classroom: &str| DefaultLesson {
+      num: Num::Actual(num.into()),
+      name: name.into(),
+      is_even: None,
+      subgroup: None,
+      teacher: Some("Иванов".into()),
+      classroom: Some(classroom.into()),
+    };
+    vec![DefaultDay {
+      day: Weekday::Mon,
+      groups: vec![DefaultGroup {
+        name: "Ит1-22".into(),
+        lessons: vec![lesson("1", "Математика", "305"), lesson("2", "Физика", "306")],
+      }],
+    }]
+  }
+
+  fn page_lesson(num: &str, name: &str, classroom: Option<&str>) -> Lesson {
+    Lesson {
+      num: Num::Actual(num.into()),
+      name: name.into(),
+      classroom: classroom.map(Into::into),
+      raw: vec![num.into(), name.into(), classroom.unwrap_or_default().into()],
+      ..Default::default()
+    }
+  }
+
+  fn replace(lessons: Vec<Lesson>) -> Vec<Lesson> {
+    let mut groups = [Group { lessons, ..Group::new("Ит1-22".into()) }];
+    replace_all_default_with(&defaults(), &mut groups, monday());
+    let [group] = groups;
+    group.lessons
+  }
+
+  #[test]
+  fn replaces_lessons_by_the_timetable() {
+    let lessons = replace(vec![
+      page_lesson("1", "По расписанию", None),
+      page_lesson("2", "по расписанию", Some("410")),
+      page_lesson("3", "По расписанию", None),
+    ]);
+
+    assert_eq!((lessons[0].name.as_str(), lessons[0].source), ("Математика", LessonSource::Default));
+    assert_eq!(lessons[0].classroom.as_deref(), Some("305"));
+    assert_eq!(lessons[0].teacher.as_deref(), Some("Иванов"));
+    assert_eq!((lessons[1].name.as_str(), lessons[1].source), ("Физика", LessonSource::DefaultWithPageClassroom));
+    assert_eq!(lessons[1].classroom.as_deref(), Some("410"));
+    assert_eq!((lessons[2].name.as_str(), lessons[2].source), ("По расписанию", LessonSource::Page));
+
+    assert_eq!(lessons[0].raw, vec!["1".to_string(), "По расписанию".into(), "".into()]);
+    assert_eq!(lessons[1].raw, vec!["2".to_string(), "по расписанию".into(), "410".into()]);
+  }
+
+  #[test]
+  fn cancelled_lessons_keep_what_they_replace() {
+    let cancelled = |num| Lesson { status: LessonStatus::Cancelled, ..page_lesson(num, "Нет", None) };
+    let lessons = replace(vec![cancelled("1"), cancelled("3")]);
+
+    let replaced = lessons[0].replaces.as_deref().unwrap();
+    assert_eq!((replaced.num.clone(), replaced.name.as_str()), (Num::Actual("1".into()), "Математика"));
+    assert_eq!(replaced.classroom.as_deref(), Some("305"));
+    assert_eq!(replaced.source, LessonSource::Default);
+    assert_eq!((lessons[0].name.as_str(), lessons[0].source), ("Нет", LessonSource::Page));
+    assert!(lessons[0].is_cancelled());
+    assert!(lessons[1].replaces.is_none());
+  }
+}
@@ -1,12 +1,18 @@
 use chrono::{DateTime, Utc};
-use maiq_shared::{Group, Lesson, Num, Snapshot};
+use log::warn;
+use maiq_shared::{Group, Lesson, LessonSource, LessonStatus, Num, Snapshot};
 use std::{iter::Peekable, slice::Iter};
 
-use super::{date, replace::replace_all_default};
+use super::{
+  date,
+  markers::{parse_markers, Markers},
+  replace::replace_all_default,
+};
 use crate::env;
 use tl_table_parser::Table;
 
 type GroupCursor = Option<String>;
+pub type Rows = Vec<Vec<String>>;
 
 #[derive(Debug, Default)]
 struct RawLesson {
@@ -16,6 +22,8 @@ struct RawLesson {
   name: Option<String>,
   teacher: Option<String>,
   classroom: Option<String>,
+  markers: Markers,
+  raw: Vec<String>,
 }
 
 macro_rules! empty_to_none {
@@ -30,17 +38,37 @@ macro_rules! empty_to_none {
 pub fn parse_snapshot(table: Table, fallback_date: DateTime<Utc>) -> anyhow::Result<Snapshot> {
   let mut rows = table.rows.into_iter();
   let date = date::parse_date(&mut rows).unwrap_or(fallback_date);
-  let mut groups = make_groups();
-  let mut group_cursor: GroupCursor = None;
+  parse_tables(vec![rows.collect()], date)
+}
+
+pub fn parse_tables(tables: Vec<Rows>, date: DateTime<Utc>) -> anyhow::Result<Snapshot> {
+  parse_tables_with(tables, date, env::groups().into(), env::discover_groups().into())
+}
+
+/// Same as [`parse_tables`], but with the known group names and group discovery given instead of read from the environment.
+pub(crate) fn parse_tables_with(
+  tables: Vec<Rows>,
+  date: DateTime<Utc>,
+  known: Vec<String>,
+  discover: bool,
+) -> anyhow::Result<Snapshot> {
+  let mut groups = make_groups(known);
   let is_name_valid = |name: &str| {
     let name = name.split(' ').next().unwrap_or_default();
-    groups.iter().any(|g| g.name == name)
+    groups.iter().any(|g| g.name == name) || (discover && is_group_name_like(name))
   };
 
-  let mut lessons = rows
-    .map(|vec| parse_row(&mut vec.iter().peekable(), &mut group_cursor, is_name_valid))
-    .collect::<Vec<RawLesson>>();
+  let mut lessons = vec![];
+  for rows in tables.iter() {
+    let mut group_cursor: GroupCursor = None;
+    lessons.extend(
+      rows
+        .iter()
+        .map(|vec| RawLesson { raw: vec.clone(), ..parse_row(&mut vec.iter().peekable(), &mut group_cursor, is_name_valid) }),
+    );
+  }
   repair_nums(&mut lessons);
+  let discovered = discover_groups(&lessons, &mut groups);
   assign_lessons_to_groups(lessons, &mut groups);
   replace_all_default(&mut groups, date);
   groups.retain(|g| !g.lessons.is_empty());
@@ -49,14 +77,35 @@ pub fn parse_snapshot(table: Table, fallback_date: DateTime<Utc>) -> anyhow::Res
     g.lessons.sort_by(|a, b| a.num.cmp(&b.num));
   });
 
-  Ok(Snapshot::new(groups, date))
+  let mut snapshot = Snapshot::new(groups, date);
+  snapshot.discovered_groups = discovered;
+  Ok(snapshot)
+}
+
+fn discover_groups(lessons: &[RawLesson], groups: &mut Vec<Group>) -> Vec<String> {
+  let mut discovered = vec![];
+  for name in lessons.iter().filter_map(|l| l.group_name.as_ref()) {
+    if groups.iter().any(|g| g.name == *name) {
+      continue;
+    }
+
+    warn!("Discovered unknown group {}", name);
+    groups.push(Group::new(name.clone()));
+    discovered.push(name.clone());
+  }
+
+  discovered
 }
 
 fn assign_lessons_to_groups(lessons: Vec<RawLesson>, groups: &mut [Group]) {
   for lesson in lessons
     .into_iter()
-    .filter(|l| l.group_name.is_some() && !matches!(l.name.as_deref(), None | Some("Нет") | Some("нет")))
+    .filter(|l| l.group_name.is_some() && l.name.is_some())
   {
+    let status = match lesson.name.as_deref() {
+      Some("Нет") | Some("нет") => LessonStatus::Cancelled,
+      _ => LessonStatus::Scheduled,
+    };
     let name = lesson.group_name.unwrap();
     let group = groups.iter_mut().find(|x| x.name == name).unwrap();
     let nums = expand_num(lesson.num);
@@ -68,6 +117,13 @@ fn assign_lessons_to_groups(lessons: Vec<RawLesson>, groups: &mut [Group]) {
         subgroup: lesson.subgroup.clone().and_then(|x| x.parse().ok()),
         teacher: lesson.teacher.clone(),
         classroom: lesson.classroom.clone(),
+        kind: lesson.markers.kind,
+        is_remote: lesson.markers.is_remote,
+        clean_name: lesson.markers.clean_name.clone(),
+        status,
+        replaces: None,
+        source: LessonSource::Page,
+        raw: lesson.raw.clone(),
       })
     }
   }
@@ -92,8 +148,9 @@ where
 
   let [name, teacher] = split_teacher(row.next().map(|x| &**x));
   let classroom = empty_to_none!(row.next());
+  let markers = name.as_deref().map(parse_markers).unwrap_or_default();
 
-  RawLesson { num, group_name, subgroup, name, teacher, classroom: classroom.cloned() }
+  RawLesson { num, group_name, subgroup, name, teacher, classroom: classroom.cloned(), markers, ..Default::default() }
 }
 
 fn parse_num(row: &mut Peekable<Iter<String>>) -> Num {
@@ -107,7 +164,10 @@ fn parse_num(row: &mut Peekable<Iter<String>>) -> Num {
 
 fn repair_nums(lessons: &mut [RawLesson]) {
   let mut iter = lessons.iter_mut();
-  let mut previous = iter.next().unwrap();
+  let mut previous = match iter.next() {
+    Some(x) => x,
+    None => return,
+  };
   for lesson in iter {
     if let Num::Previous = lesson.num {
       lesson.num = previous.num.clone();
@@ -130,13 +190,94 @@ fn __test_is_num() {
   assert!(is_num(""));
 }
 
+pub(crate) fn is_group_name_like(name: &str) -> bool {
+  let (head, year) = match name.split_once('-') {
+    Some(x) => x,
+    None => return false,
+  };
+  let letters = head.trim_end_matches(|c: char| c.is_ascii_digit());
+
+  year.len() == 2
+    && year.chars().all(|c| c.is_ascii_digit())
+    && letters.len() < head.len()
+    && !letters.is_empty()
+    && letters.chars().all(char::is_alphabetic)
+}
+
+#[cfg(test)]
+#[test]
+fn __test_is_group_name_like() {
+  assert!(is_group_name_like("Ит1-23"));
+  assert!(is_group_name_like("ЗК1-22"));
+  assert!(!is_group_name_like("Ит-23"));
+  assert!(!is_group_name_like("1-23"));
+  assert!(!is_group_name_like("Ит1-2023"));
+  assert!(!is_group_name_like("По расписанию"));
+}
+
+#[cfg(test)]
+#[test]
+fn __test_keeps_cancelled_lessons() {
+  use maiq_shared::Uid;
+
+  let rows = vec![
+    vec!["Ит1-22".to_string(), "1".into(), "Математика, Иванов".into(), "305".into()],
+    vec!["2".to_string(), "Нет".into(), "".into()],
+  ];
+  let snapshot = parse_tables(vec![rows], maiq_shared::utils::time::now_date()).unwrap();
+  let lessons = &snapshot.group("Ит1-22").unwrap().lessons;
+  assert_eq!(lessons.len(), 2);
+  assert!(!lessons[0].is_cancelled());
+  assert!(lessons[1].is_cancelled());
+  assert_eq!(lessons[1].num, Num::Actual("2".into()));
+  assert_eq!(lessons[1].source, LessonSource::Page);
+  assert_eq!(lessons[1].raw, vec!["2".to_string(), "Нет".into(), "".into()]);
+
+  let mut restored = snapshot.clone();
+  restored.groups[0].lessons[1].status = LessonStatus::Scheduled;
+  restored.refresh();
+  assert_ne!(restored.groups[0].uid, snapshot.groups[0].uid);
+  assert_ne!(restored.uid, snapshot.uid);
+}
+
+#[cfg(test)]
+#[test]
+fn __test_discovers_groups() {
+  let rows = vec![
+    vec!["Ит1-22".to_string(), "1".into(), "Математика".into(), "".into()],
+    vec!["Зк2-23 1".to_string(), "1".into(), "Физика, Петров".into(), "201".into()],
+    vec!["2".to_string(), "История".into(), "".into()],
+  ];
+  let junk = vec![vec!["Всего часов".to_string(), "3".into(), "".into()], vec!["Ит-23".to_string(), "1".into(), "Химия".into()]];
+  let tables = vec![rows, junk];
+  let date = maiq_shared::utils::time::now_date();
+
+  let snapshot = parse_tables_with(tables.clone(), date, vec!["Ит1-22".into()], true).unwrap();
+  assert_eq!(snapshot.discovered_groups, vec!["Зк2-23".to_string()]);
+  let lessons = &snapshot.group("Зк2-23").unwrap().lessons;
+  assert_eq!(lessons.iter().map(|l| l.name.as_str()).collect::<Vec<_>>(), ["Физика", "История"]);
+  assert!(lessons.iter().all(|l| l.subgroup == Some(1)));
+  assert_eq!(snapshot.groups.iter().map(|g| g.name.as_str()).collect::<Vec<_>>(), ["Ит1-22", "Зк2-23"]);
+
+  let snapshot = parse_tables_with(tables, date, vec!["Ит1-22".into()], false).unwrap();
+  assert!(snapshot.discovered_groups.is_empty());
+  assert!(snapshot.group("Зк2-23").is_none());
+}
+
 fn expand_num(num: Num) -> Vec<Num> {
-  match num {
+  let nums = match num {
     Num::Actual(x) => x
       .split(',')
-      .map(|x| Num::Actual(x.trim().to_string()))
+      .map(|x| x.trim())
+      .filter(|x| !x.is_empty())
+      .map(|x| Num::Actual(x.to_string()))
       .collect::<Vec<Num>>(),
-    _ => vec![Num::None],
+    _ => vec![],
+  };
+
+  match nums.is_empty() {
+    true => vec![Num::None],
+    false => nums,
   }
 }
 
@@ -161,8 +302,7 @@ fn split_group_name(raw: Option<&str>) -> [Option<String>; 2] {
   [empty_to_none!(split.next().map(|x| x.to_string())), empty_to_none!(split.next().map(|x| x.to_string()))]
 }
 
-fn make_groups() -> Vec<Group> {
-  let names: Vec<String> = env::groups().into();
+fn make_groups(names: Vec<String>) -> Vec<Group> {
   let mut groups = Vec::with_capacity(names.len());
   for name in names.into_iter() {
     groups.push(Group::new(name));
@@ -170,3 +310,76 @@ fn make_groups() -> Vec<Group> {
 
   groups
 }
+
+#[cfg(test)]
+mod props {
+  use maiq_shared::{Num, Snapshot};
+  use proptest::prelude::*;
+
+  use super::{expand_num, parse_row, parse_tables_with, split_group_name, split_teacher, Rows};
+
+  const ANCHOR: &str = "Ит1-22";
+
+  fn known_groups() -> Vec<String> {
+    vec![ANCHOR.into(), "Ир3-21".into(), "Са1-22".into()]
+  }
+
+  fn group_cell() -> impl Strategy<Value = String> {
+    prop_oneof![
+      (prop::sample::select(known_groups()), prop::option::of(1u8..3))
+        .prop_map(|(name, sub)| sub.map(|sub| format!("{} {}", name, sub)).unwrap_or(name)),
+      "[0-9, ]{0,6}",
+      any::<String>(),
+    ]
+  }
+
+  fn row() -> impl Strategy<Value = Vec<String>> {
+    (group_cell(), prop::collection::vec(prop_oneof!["[0-9,()ч ]{0,8}", "[а-яА-Я.,() ]{0,24}", any::<String>()], 0..5))
+      .prop_map(|(head, tail)| [vec![head], tail].concat())
+  }
+
+  fn table() -> impl Strategy<Value = Rows> {
+    prop::collection::vec(row(), 0..24)
+  }
+
+  fn parse(tables: Vec<Rows>) -> Snapshot {
+    parse_tables_with(tables, maiq_shared::utils::time::now_date(), known_groups(), false).unwrap()
+  }
+
+  proptest! {
+    #[test]
+    fn row_helpers_do_not_panic(cells in prop::collection::vec(any::<String>(), 0..6), raw in any::<String>()) {
+      let mut cursor = None;
+      parse_row(&mut cells.iter().peekable(), &mut cursor, |x| x.starts_with('И'));
+      let [_, teacher] = split_teacher(Some(&raw));
+      prop_assert!(!teacher.unwrap_or_default().contains(','));
+      let [name, _] = split_group_name(Some(&raw));
+      prop_assert!(!name.unwrap_or_default().contains(' '));
+    }
+
+    #[test]
+    fn nums_are_expanded(raw in "[0-9, ]{0,12}") {
+      for num in expand_num(Num::Actual(raw)) {
+        prop_assert!(matches!(num, Num::None) || matches!(num, Num::Actual(ref x) if !x.is_empty() && !x.contains(',')));
+      }
+    }
+
+    #[test]
+    fn parsed_snapshot_invariants(mut tables in prop::collection::vec(table(), 1..3)) {
+      let known = known_groups();
+      tables[0].insert(0, vec![ANCHOR.into(), "1".into(), "Математика, Иванов".into(), "305".into()]);
+      let snapshot = parse(tables.clone());
+
+      prop_assert!(snapshot.group(ANCHOR).is_some());
+
+      for group in snapshot.groups.iter() {
+        prop_assert!(known.contains(&group.name));
+        prop_assert!(!group.lessons.is_empty());
+        prop_assert!(group.lessons.windows(2).all(|w| w[0].num < w[1].num || (w[0].num == w[1].num && w[0].subgroup <= w[1].subgroup)));
+        prop_assert!(group.lessons.iter().all(|l| !matches!(l.num, Num::Actual(ref x) if x.contains(','))));
+      }
+
+      prop_assert_eq!(snapshot.uid, parse(tables).uid);
+    }
+  }
+}
@@ -0,0 +1,256 @@
+use chrono::{DateTime, Duration, Utc};
+use maiq_shared::{
+  bells::{Bell, BellSchedule},
+  default::DefaultDay,
+  utils::time::date_of,
+  DayKind, Lesson, Snapshot,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::parser::replace::{default_lessons_with, REPLACEMENTS};
+
+const LOOKAHEAD_DAYS: i64 = 14;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Scheduled {
+  pub date: DateTime<Utc>,
+  pub lesson: Lesson,
+  pub bell: Option<Bell>,
+}
+
+/// Queries over a single group. Days covered by one of the given snapshots are taken from it,
+/// any other day, or a working day whose snapshot doesn't list the group, falls back to the default timetable.
+#[derive(Debug, Clone)]
+pub struct GroupQuery<'a> {
+  group: &'a str,
+  subgroup: Option<u8>,
+  snapshots: Vec<&'a Snapshot>,
+  bells: Option<&'a BellSchedule>,
+  defaults: &'a [DefaultDay],
+}
+
+impl<'a> GroupQuery<'a> {
+  pub fn new(group: &'a str) -> Self {
+    Self { group, subgroup: None, snapshots: vec![], bells: None, defaults: &REPLACEMENTS }
+  }
+
+  pub fn subgroup(mut self, subgroup: Option<u8>) -> Self {
+    self.subgroup = subgroup;
+    self
+  }
+
+  pub fn snapshot(mut self, snapshot: Option<&'a Snapshot>) -> Self {
+    self.snapshots.extend(snapshot);
+    self
+  }
+
+  pub fn bells(mut self, bells: Option<&'a BellSchedule>) -> Self {
+    self.bells = bells;
+    self
+  }
+
+  /// Default timetable to fall back to, the bundled one unless set.
+  pub fn defaults(mut self, defaults: &'a [DefaultDay]) -> Self {
+    self.defaults = defaults;
+    self
+  }
+
+  pub fn kind(&self, date: DateTime<Utc>) -> DayKind {
+    self.snapshot_for(date).map(|s| s.kind).unwrap_or_default()
+  }
+
+  pub fn lessons(&self, date: DateTime<Utc>) -> Vec<Lesson> {
+    let mut lessons = match self.snapshot_for(date) {
+      Some(snapshot) => match snapshot.group(self.group) {
+        Some(group) => group.lessons.clone(),
+        // A group without replacements may be left out of the page
+        None if matches!(snapshot.kind, DayKind::Regular | DayKind::Shortened) => {
+          default_lessons_with(self.defaults, self.group, date_of(date))
+        }
+        None => vec![],
+      },
+      None => default_lessons_with(self.defaults, self.group, date_of(date)),
+    };
+    lessons.retain(|l| l.is_for(self.subgroup) && !l.is_cancelled());
+    lessons
+  }
+
+  pub fn count(&self, date: DateTime<Utc>) -> usize {
+    self.lessons(date).len()
+  }
+
+  pub fn is_free(&self, date: DateTime<Utc>) -> bool {
+    self.lessons(date).is_empty()
+  }
+
+  pub fn first(&self, date: DateTime<Utc>) -> Option<Scheduled> {
+    self.scheduled(date).into_iter().next()
+  }
+
+  pub fn last(&self, date: DateTime<Utc>) -> Option<Scheduled> {
+    self.scheduled(date).pop()
+  }
+
+  /// Requires a bell schedule, otherwise there is no way to tell which lesson is going on.
+  pub fn current(&self, now: DateTime<Utc>) -> Option<Scheduled> {
+    self.bells?;
+    self
+      .scheduled(now)
+      .into_iter()
+      .find(|s| matches!(s.bell, Some(ref b) if b.contains(now.time())))
+  }
+
+  /// Without a bell schedule only the following days are considered.
+  pub fn next(&self, now: DateTime<Utc>) -> Option<Scheduled> {
+    self
+      .scheduled(now)
+      .into_iter()
+      .find(|s| matches!(s.bell, Some(ref b) if b.start > now.time()))
+      .or_else(|| self.next_day_with_lessons(now).and_then(|date| self.first(date)))
+  }
+
+  pub fn next_day_with_lessons(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    (1..=LOOKAHEAD_DAYS)
+      .map(|offset| date_of(after) + Duration::days(offset))
+      .find(|date| !self.is_free(*date))
+  }
+
+  fn snapshot_for(&self, date: DateTime<Utc>) -> Option<&'a Snapshot> {
+    self
+      .snapshots
+      .iter()
+      .find(|s| s.date.date_naive() == date.date_naive())
+      .copied()
+  }
+
+  fn scheduled(&self, date: DateTime<Utc>) -> Vec<Scheduled> {
+    let date = date_of(date);
+    let bells = self.bells.map(|b| b.for_day(self.kind(date)));
+    self
+      .lessons(date)
+      .into_iter()
+      .map(|lesson| {
+        let bell = bells.as_ref().and_then(|b| b.get(&lesson.num)).cloned();
+        Scheduled { date, lesson, bell }
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::{DateTime, NaiveTime, TimeZone, Utc, Weekday};
+  use maiq_shared::{
+    bells::{Bell, BellSchedule},
+    default::{DefaultDay, DefaultGroup, DefaultLesson},
+    DayKind, Group, Lesson, Num, Snapshot,
+  };
+
+  use super::GroupQuery;
+
+  fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2023, 9, day, hour, minute, 0).unwrap()
+  }
+
+  fn snapshot(day: u32, group: &str, lessons: &[(&str, &str)]) -> Snapshot {
+    let lessons = lessons
+      .iter()
+      .map(|(num, name)| Lesson { num: Num::Actual(num.to_string()), name: name.to_string(), ..Default::default() })
+      .collect();
+    Snapshot::new(vec![Group { lessons, ..Group::new(group.into()) }], at(day, 0, 0))
+  }
+
+  fn bells() -> BellSchedule {
+    let bell = |num: &str, start: (u32, u32), end: (u32, u32)| Bell {
+      num: num.into(),
+      start: NaiveTime::from_hms_opt(start.0, start.1, 0).unwrap(),
+      end: NaiveTime::from_hms_opt(end.0, end.1, 0).unwrap(),
+    };
+    BellSchedule::new(vec![bell("1", (8, 0), (9, 30)), bell("2", (9, 40), (11, 10)), bell("3", (11, 30), (13, 0))])
+  }
+
+  #[test]
+  fn follows_the_bells() {
+    let (monday, tuesday, wednesday) = (
+      snapshot(11, "Ит1-22", &[("2", "Математика"), ("3", "Физика")]),
+      snapshot(12, "Ит1-22", &[("1", "История")]),
+      snapshot(13, "Ир3-21", &[("1", "Химия")]),
+    );
+    let bells = bells();
+    let query = GroupQuery::new("Ит1-22")
+      .snapshot(Some(&monday))
+      .snapshot(Some(&tuesday))
+      .snapshot(Some(&wednesday))
+      .bells(Some(&bells));
+    let name = |x: Option<super::Scheduled>| x.map(|s| s.lesson.name);
+
+    assert_eq!(name(query.first(at(11, 7, 0))).as_deref(), Some("Математика"));
+    assert_eq!(name(query.last(at(11, 7, 0))).as_deref(), Some("Физика"));
+
+    assert!(query.current(at(11, 7, 0)).is_none());
+    assert_eq!(name(query.next(at(11, 7, 0))).as_deref(), Some("Математика"));
+
+    assert_eq!(name(query.current(at(11, 10, 0))).as_deref(), Some("Математика"));
+    assert!(query.current(at(11, 11, 20)).is_none());
+    assert_eq!(name(query.next(at(11, 11, 20))).as_deref(), Some("Физика"));
+
+    let next = query.next(at(11, 14, 0)).unwrap();
+    assert!(query.current(at(11, 14, 0)).is_none());
+    assert_eq!((next.date, next.lesson.name.as_str()), (at(12, 0, 0), "История"));
+    assert_eq!(next.bell.map(|b| b.num).as_deref(), Some("1"));
+
+    assert!(query.is_free(at(13, 9, 0)));
+    assert_eq!(query.count(at(13, 9, 0)), 0);
+    assert_eq!(query.next_day_with_lessons(at(11, 14, 0)), Some(at(12, 0, 0)));
+  }
+
+  #[test]
+  fn current_needs_bells() {
+    let monday = snapshot(11, "Ит1-22", &[("1", "Математика"), ("2", "Физика")]);
+    let tuesday = snapshot(12, "Ит1-22", &[("1", "История")]);
+    let query = GroupQuery::new("Ит1-22")
+      .snapshot(Some(&monday))
+      .snapshot(Some(&tuesday));
+
+    assert!(query.current(at(11, 8, 30)).is_none());
+    let next = query.next(at(11, 8, 30)).unwrap();
+    assert_eq!((next.date, next.lesson.name.as_str()), (at(12, 0, 0), "История"));
+    assert!(next.bell.is_none());
+  }
+
+  #[test]
+  fn groups_left_out_follow_the_timetable() {
+    let defaults = vec![DefaultDay {
+      day: Weekday::Mon,
+      groups: vec![DefaultGroup {
+        name: "Ит1-22".into(),
+        lessons: vec![DefaultLesson {
+          num: Num::Actual("1".into()),
+          name: "Математика".into(),
+          is_even: None,
+          subgroup: None,
+          teacher: None,
+          classroom: None,
+        }],
+      }],
+    }];
+    let mut monday = snapshot(11, "Ир3-21", &[("1", "Химия")]);
+    let query = GroupQuery::new("Ит1-22").snapshot(Some(&monday)).defaults(&defaults);
+    let names = |query: &GroupQuery| {
+      query
+        .lessons(at(11, 7, 0))
+        .into_iter()
+        .map(|l| l.name)
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(names(&query), ["Математика"]);
+    let listed = snapshot(11, "Ит1-22", &[("2", "Физика")]);
+    assert_eq!(names(&GroupQuery::new("Ит1-22").snapshot(Some(&listed)).defaults(&defaults)), ["Физика"]);
+
+    monday.kind = DayKind::Holiday;
+    let query = GroupQuery::new("Ит1-22").snapshot(Some(&monday)).defaults(&defaults);
+    assert!(query.is_free(at(11, 7, 0)));
+  }
+}
@@ -0,0 +1,331 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+  extract::{Path, Query, State},
+  http::{
+    header::{ETAG, IF_NONE_MATCH},
+    HeaderMap, StatusCode,
+  },
+  response::{IntoResponse, Response},
+  routing::get,
+  Json, Router,
+};
+use chrono::{NaiveDate, Weekday};
+use log::{error, info};
+use maiq_shared::{compare::distinct, legacy::VersionedUid, lookup::Lookup, utils::UID_LEN, Fetch, Snapshot, Uid};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, RwLock};
+
+use crate::{
+  fetch::{FetchCache, Fetcher},
+  lookup_default,
+  store::SnapshotStore,
+};
+
+#[derive(Debug, Default)]
+struct Snapshots {
+  today: Option<Snapshot>,
+  next: Option<Snapshot>,
+  previous_today: Option<Snapshot>,
+  previous_next: Option<Snapshot>,
+}
+
+#[derive(Clone)]
+pub struct AppState {
+  snapshots: Arc<RwLock<Snapshots>>,
+  fetcher: Arc<Fetcher>,
+  cache: Arc<Mutex<FetchCache>>,
+  store: Option<SnapshotStore>,
+}
+
+#[derive(Deserialize)]
+struct GroupParams {
+  subgroup: Option<u8>,
+}
+
+#[derive(Serialize)]
+struct Diff<'a> {
+  today: Vec<String>,
+  next: Vec<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  today_uid: Option<&'a str>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  next_uid: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct NotFound {
+  suggestions: Vec<String>,
+}
+
+impl AppState {
+  pub fn new(fetcher: Fetcher, store: Option<SnapshotStore>) -> Self {
+    Self {
+      snapshots: Arc::new(RwLock::new(Snapshots::default())),
+      fetcher: Arc::new(fetcher),
+      cache: Arc::new(Mutex::new(FetchCache::new())),
+      store,
+    }
+  }
+
+  pub async fn refresh(&self) {
+    let mut cache = self.cache.lock().await;
+    let today = self.fetcher.snapshot_cached(&Fetch::Today, &mut cache).await;
+    let next = self.fetcher.snapshot_cached(&Fetch::Next, &mut cache).await;
+    let mut snapshots = self.snapshots.write().await;
+
+    match today {
+      Ok(s) => {
+        if let Some(prev) = self.replace(&mut snapshots.today, s) {
+          snapshots.previous_today = Some(prev);
+        }
+      }
+      Err(e) => error!("Unable to fetch today: {}", e),
+    }
+
+    match next {
+      Ok(s) => {
+        if let Some(prev) = self.replace(&mut snapshots.next, s) {
+          snapshots.previous_next = Some(prev);
+        }
+      }
+      Err(e) => error!("Unable to fetch next: {}", e),
+    }
+  }
+
+  fn replace(&self, slot: &mut Option<Snapshot>, new: Snapshot) -> Option<Snapshot> {
+    if matches!(slot, Some(ref s) if new.matches_uid(&s.uid)) {
+      return None;
+    }
+
+    info!("New snapshot {} for {}", new.uid, new.date);
+    if let Some(Err(e)) = self.store.as_ref().map(|store| store.save(&new)) {
+      error!("Unable to save snapshot {}: {}", new.uid, e);
+    }
+    slot.replace(new)
+  }
+}
+
+pub fn router(state: AppState) -> Router {
+  Router::new()
+    .route("/today", get(today))
+    .route("/next", get(next))
+    .route("/today/:group", get(today_group))
+    .route("/next/:group", get(next_group))
+    .route("/history/:date", get(history))
+    .route("/defaults/:weekday/:group", get(defaults))
+    .route("/diff", get(diff))
+    .with_state(state)
+}
+
+pub async fn poll(state: AppState, every: Duration) {
+  let mut interval = tokio::time::interval(every);
+  loop {
+    interval.tick().await;
+    state.refresh().await;
+  }
+}
+
+async fn today(State(state): State<AppState>, headers: HeaderMap) -> Response {
+  let snapshots = state.snapshots.read().await;
+  match snapshots.today.as_ref() {
+    Some(s) => tagged(&headers, &s.uid, s),
+    None => StatusCode::NOT_FOUND.into_response(),
+  }
+}
+
+async fn next(State(state): State<AppState>, headers: HeaderMap) -> Response {
+  let snapshots = state.snapshots.read().await;
+  match snapshots.next.as_ref() {
+    Some(s) => tagged(&headers, &s.uid, s),
+    None => StatusCode::NOT_FOUND.into_response(),
+  }
+}
+
+async fn today_group(
+  State(state): State<AppState>,
+  Path(group): Path<String>,
+  Query(params): Query<GroupParams>,
+  headers: HeaderMap,
+) -> Response {
+  let snapshots = state.snapshots.read().await;
+  group_response(snapshots.today.as_ref(), &group, params.subgroup, &headers)
+}
+
+async fn next_group(
+  State(state): State<AppState>,
+  Path(group): Path<String>,
+  Query(params): Query<GroupParams>,
+  headers: HeaderMap,
+) -> Response {
+  let snapshots = state.snapshots.read().await;
+  group_response(snapshots.next.as_ref(), &group, params.subgroup, &headers)
+}
+
+async fn history(State(state): State<AppState>, Path(date): Path<String>, headers: HeaderMap) -> Response {
+  let date = match NaiveDate::parse_from_str(&date, "%Y-%m-%d") {
+    Ok(d) => d,
+    Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+  };
+
+  let store = match state.store.as_ref() {
+    Some(s) => s,
+    None => return StatusCode::NOT_FOUND.into_response(),
+  };
+
+  match store.versions(date) {
+    Ok(versions) => {
+      let uids = versions
+        .iter()
+        .map(|s| s.uid.as_str())
+        .collect::<Vec<&str>>()
+        .join("-");
+      tagged(&headers, &uids, &versions)
+    }
+    Err(e) => {
+      error!("Unable to read history for {}: {}", date, e);
+      StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    }
+  }
+}
+
+async fn defaults(Path((weekday, group)): Path<(String, String)>) -> Response {
+  let weekday = match weekday.parse::<Weekday>() {
+    Ok(w) => w,
+    Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+  };
+
+  match lookup_default(weekday, &group) {
+    Lookup::Found(g) => Json(g).into_response(),
+    Lookup::Missing(suggestions) => (StatusCode::NOT_FOUND, Json(NotFound { suggestions })).into_response(),
+  }
+}
+
+async fn diff(State(state): State<AppState>, headers: HeaderMap) -> Response {
+  let snapshots = state.snapshots.read().await;
+  let diff = Diff {
+    today: distinct(snapshots.previous_today.as_ref(), snapshots.today.as_ref()),
+    next: distinct(snapshots.previous_next.as_ref(), snapshots.next.as_ref()),
+    today_uid: snapshots.today.as_ref().map(|s| s.uid.as_str()),
+    next_uid: snapshots.next.as_ref().map(|s| s.uid.as_str()),
+  };
+  let uid = format!("{}-{}", diff.today_uid.unwrap_or_default(), diff.next_uid.unwrap_or_default());
+  tagged(&headers, &uid, &diff)
+}
+
+fn group_response(snapshot: Option<&Snapshot>, query: &str, subgroup: Option<u8>, headers: &HeaderMap) -> Response {
+  let snapshot = match snapshot {
+    Some(s) => s,
+    None => return StatusCode::NOT_FOUND.into_response(),
+  };
+
+  match snapshot.lookup_group(query) {
+    Lookup::Found(g) => {
+      let tiny = snapshot.tiny_personal(&g.name, subgroup);
+      let uid = format!("{}-{}", tiny.date.format("%Y%m%d"), tiny.uid_with_len(UID_LEN));
+      tagged(headers, &uid, &tiny)
+    }
+    Lookup::Missing(suggestions) => (StatusCode::NOT_FOUND, Json(NotFound { suggestions })).into_response(),
+  }
+}
+
+fn tagged<T: Serialize>(headers: &HeaderMap, uid: &str, body: &T) -> Response {
+  let etag = format!("\"{}\"", uid);
+  let is_fresh = headers
+    .get(IF_NONE_MATCH)
+    .and_then(|x| x.to_str().ok())
+    .map(|x| {
+      x.split(',')
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag)
+    })
+    .unwrap_or(false);
+
+  match is_fresh {
+    true => (StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response(),
+    false => ([(ETAG, etag)], Json(body)).into_response(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::{
+    body::Body,
+    http::{
+      header::{ETAG, IF_NONE_MATCH},
+      Request, StatusCode,
+    },
+    response::Response,
+  };
+  use maiq_shared::{utils::time::now, Group, Lesson, Num, Snapshot};
+  use tower::ServiceExt;
+
+  use super::{router, AppState};
+  use crate::fetch::Fetcher;
+
+  async fn state() -> (AppState, Snapshot) {
+    let lesson = Lesson { num: Num::Actual("1".into()), name: "Математика".into(), ..Default::default() };
+    let snapshot = Snapshot::new(vec![Group { lessons: vec![lesson], ..Group::new("Ит1-22".into()) }], now());
+    let state = AppState::new(Fetcher::new(), None);
+    state.snapshots.write().await.today = Some(snapshot.clone());
+    (state, snapshot)
+  }
+
+  async fn get(state: AppState, uri: &str, etag: Option<&str>) -> Response {
+    let uri = uri
+      .bytes()
+      .map(|b| match b.is_ascii() {
+        true => (b as char).to_string(),
+        false => format!("%{:02X}", b),
+      })
+      .collect::<String>();
+    let mut request = Request::get(uri);
+    if let Some(etag) = etag {
+      request = request.header(IF_NONE_MATCH, etag);
+    }
+    router(state)
+      .oneshot(request.body(Body::empty()).unwrap())
+      .await
+      .unwrap()
+  }
+
+  async fn body(res: Response) -> serde_json::Value {
+    let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    serde_json::from_slice(&bytes).unwrap()
+  }
+
+  #[tokio::test]
+  async fn serves_snapshots_with_etag() {
+    let (state, snapshot) = state().await;
+    let res = get(state.clone(), "/today", None).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let etag = res.headers()[ETAG].to_str().unwrap().to_string();
+    assert_eq!(etag, format!("\"{}\"", snapshot.uid));
+    assert_eq!(body(res).await["uid"], snapshot.uid);
+
+    let res = get(state.clone(), "/today", Some(&etag)).await;
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(res.headers()[ETAG], etag.as_str());
+    assert_eq!(
+      get(state.clone(), "/today", Some("\"other\", W/\"x\""))
+        .await
+        .status(),
+      StatusCode::OK
+    );
+    assert_eq!(get(state, "/next", None).await.status(), StatusCode::NOT_FOUND);
+  }
+
+  #[tokio::test]
+  async fn looks_groups_up() {
+    let (state, _) = state().await;
+    let res = get(state.clone(), "/today/ит1-22", None).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let etag = res.headers()[ETAG].to_str().unwrap().to_string();
+    assert_eq!(body(res).await["group"]["name"], "Ит1-22");
+    assert_eq!(get(state.clone(), "/today/Ит1-22", Some(&etag)).await.status(), StatusCode::NOT_MODIFIED);
+
+    let res = get(state, "/today/Ит1-23", None).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    assert_eq!(body(res).await["suggestions"][0], "Ит1-22");
+  }
+}
@@ -0,0 +1,132 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use encoding_rs::Encoding;
+use maiq_shared::{FetchUrl, Snapshot};
+
+use crate::{charset, snapshot_from_html};
+
+#[derive(Debug, Clone, Default)]
+pub struct RawPage {
+  pub bytes: Vec<u8>,
+  pub content_type: Option<String>,
+  pub etag: Option<String>,
+  pub last_modified: Option<String>,
+}
+
+impl RawPage {
+  pub fn new(bytes: Vec<u8>) -> Self {
+    Self { bytes, ..Default::default() }
+  }
+
+  pub fn decode(&self) -> (String, &'static Encoding) {
+    charset::decode(&self.bytes, self.content_type.as_deref())
+  }
+
+  pub fn snapshot(&self, fallback_date: DateTime<Utc>) -> anyhow::Result<Snapshot> {
+    let (html, encoding) = self.decode();
+    let mut snapshot = snapshot_from_html(&html, fallback_date)?;
+    snapshot.encoding = Some(encoding.name().into());
+    Ok(snapshot)
+  }
+}
+
+#[async_trait]
+pub trait ScheduleSource {
+  async fn fetch_raw(&self, target: &(dyn FetchUrl + Sync)) -> anyhow::Result<RawPage>;
+}
+
+pub async fn snapshot_from_source<S>(source: &S, target: &(dyn FetchUrl + Sync)) -> anyhow::Result<Snapshot>
+where
+  S: ScheduleSource + ?Sized,
+{
+  source.fetch_raw(target).await?.snapshot(target.date())
+}
+
+/// Reads pages from a directory, using the last segment of the target url as a file name.
+#[derive(Debug, Clone)]
+pub struct FileSource {
+  dir: PathBuf,
+}
+
+impl FileSource {
+  pub fn new(dir: impl Into<PathBuf>) -> Self {
+    Self { dir: dir.into() }
+  }
+
+  pub fn path(&self, target: &(dyn FetchUrl + Sync)) -> PathBuf {
+    let url = target.url();
+    self.dir.join(url.rsplit('/').next().unwrap_or(url))
+  }
+}
+
+#[async_trait]
+impl ScheduleSource for FileSource {
+  async fn fetch_raw(&self, target: &(dyn FetchUrl + Sync)) -> anyhow::Result<RawPage> {
+    let path = self.path(target);
+    let bytes = tokio::fs::read(&path)
+      .await
+      .map_err(|e| anyhow::anyhow!("Unable to read {}: {}", path.display(), e))?;
+    Ok(RawPage::new(bytes))
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MemorySource {
+  pages: HashMap<String, RawPage>,
+}
+
+impl MemorySource {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn insert(&mut self, url: impl Into<String>, page: RawPage) {
+    self.pages.insert(url.into(), page);
+  }
+}
+
+#[async_trait]
+impl ScheduleSource for MemorySource {
+  async fn fetch_raw(&self, target: &(dyn FetchUrl + Sync)) -> anyhow::Result<RawPage> {
+    self
+      .pages
+      .get(target.url())
+      .cloned()
+      .ok_or_else(|| anyhow::anyhow!("No page for {}", target.url()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{FileSource, MemorySource, RawPage, ScheduleSource};
+  use maiq_shared::Fetch;
+
+  #[tokio::test]
+  async fn memory_source_serves_inserted_pages() {
+    let mut source = MemorySource::new();
+    source.insert("https://rsp.chemk.org/4korp/today.htm", RawPage::new(vec![0xcf, 0xe0, 0xf0, 0xe0]));
+
+    assert_eq!(source.fetch_raw(&Fetch::Today).await.unwrap().decode().0, "Пара");
+    assert!(source.fetch_raw(&Fetch::Next).await.is_err());
+  }
+
+  #[test]
+  fn file_source_uses_url_file_name() {
+    let source = FileSource::new("pages");
+    assert_eq!(source.path(&Fetch::Next), std::path::Path::new("pages/tomorrow.htm"));
+  }
+
+  #[tokio::test]
+  async fn file_source_reads_pages() {
+    let dir = std::env::temp_dir().join(format!("maiq-file-source-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("today.htm"), [0xcf, 0xe0, 0xf0, 0xe0]).unwrap();
+
+    let source = FileSource::new(&dir);
+    assert_eq!(source.fetch_raw(&Fetch::Today).await.unwrap().decode().0, "Пара");
+    assert!(source.fetch_raw(&Fetch::Next).await.is_err());
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+}
@@ -0,0 +1,124 @@
+use std::{
+  fs,
+  io::{BufReader, BufWriter},
+  path::PathBuf,
+};
+
+use chrono::NaiveDate;
+use log::warn;
+use maiq_shared::{
+  schema,
+  timeline::{self, Version},
+  Snapshot,
+};
+
+const DATE_FORMAT: &str = "%d-%m-%Y";
+
+pub fn file_name(snapshot: &Snapshot) -> String {
+  format!("{}_{}.json", snapshot.date.format(DATE_FORMAT), snapshot.uid)
+}
+
+/// Directory of snapshot dumps, one file per version, named the same way as `dump-today` / `dump-next` do.
+#[derive(Debug, Clone)]
+pub struct SnapshotStore {
+  dir: PathBuf,
+}
+
+impl SnapshotStore {
+  pub fn new(dir: impl Into<PathBuf>) -> anyhow::Result<Self> {
+    let dir = dir.into();
+    fs::create_dir_all(&dir)?;
+    Ok(Self { dir })
+  }
+
+  /// Store over an existing directory, for reading only: the directory is not created.
+  pub fn open(dir: impl Into<PathBuf>) -> Self {
+    Self { dir: dir.into() }
+  }
+
+  pub fn save(&self, snapshot: &Snapshot) -> anyhow::Result<PathBuf> {
+    let path = self.dir.join(file_name(snapshot));
+    if !path.exists() {
+      let writer = BufWriter::new(fs::File::create(&path)?);
+      serde_json::to_writer_pretty(writer, snapshot)?;
+    }
+    Ok(path)
+  }
+
+  pub fn versions(&self, date: NaiveDate) -> anyhow::Result<Vec<Snapshot>> {
+    let prefix = format!("{}_", date.format(DATE_FORMAT));
+    let mut snapshots = vec![];
+    for entry in fs::read_dir(&self.dir)? {
+      let path = entry?.path();
+      let is_version = path.extension().map(|ext| ext == "json").unwrap_or(false)
+        && path
+          .file_name()
+          .and_then(|x| x.to_str())
+          .map(|x| x.starts_with(&prefix))
+          .unwrap_or(false);
+      if !is_version {
+        continue;
+      }
+
+      // `dump -g` writes group dumps under the same name, and anything else may lie around
+      let snapshot = fs::File::open(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| Ok(schema::from_reader::<_, Snapshot>(BufReader::new(file))?));
+      match snapshot {
+        Ok(x) => snapshots.push(x),
+        Err(e) => warn!("Skipping {}: {}", path.display(), e),
+      }
+    }
+
+    snapshots.sort_by_key(|s| s.parsed_date);
+    Ok(snapshots)
+  }
+
+  pub fn latest(&self, date: NaiveDate) -> anyhow::Result<Option<Snapshot>> {
+    Ok(self.versions(date)?.pop())
+  }
+
+  pub fn timeline(&self, date: NaiveDate, group: &str) -> anyhow::Result<Vec<Version>> {
+    Ok(timeline::timeline(&self.versions(date)?, date, group))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::{Duration, TimeZone, Utc};
+  use maiq_shared::{Group, Lesson, Num, Snapshot};
+
+  use super::{file_name, SnapshotStore};
+
+  fn snapshot(day: u32, hour: i64, lesson: &str) -> Snapshot {
+    let date = Utc.with_ymd_and_hms(2023, 9, day, 0, 0, 0).unwrap();
+    let lessons = vec![Lesson { num: Num::Actual("1".into()), name: lesson.into(), ..Default::default() }];
+    let mut snapshot = Snapshot::new(vec![Group { lessons, ..Group::new("Ит1-22".into()) }], date);
+    snapshot.parsed_date = date + Duration::hours(hour);
+    snapshot
+  }
+
+  #[test]
+  fn timeline_skips_foreign_files() {
+    let dir = std::env::temp_dir().join(format!("maiq-store-{}", std::process::id()));
+    let store = SnapshotStore::new(&dir).unwrap();
+    let (first, second) = (snapshot(11, 7, "Математика"), snapshot(11, 9, "Физика"));
+    store.save(&first).unwrap();
+    store.save(&second).unwrap();
+    store.save(&snapshot(12, 7, "История")).unwrap();
+
+    let tiny = snapshot(11, 8, "Химия");
+    std::fs::write(dir.join(file_name(&tiny)), serde_json::to_string(&tiny.tiny("Ит1-22")).unwrap()).unwrap();
+    std::fs::write(dir.join("11-09-2023_notes.json"), "[1, 2, 3]").unwrap();
+    std::fs::write(dir.join("11-09-2023_broken.json"), "{").unwrap();
+
+    let date = first.date.date_naive();
+    let versions = SnapshotStore::open(&dir).timeline(date, "Ит1-22").unwrap();
+    assert_eq!(versions.iter().map(|v| &v.snapshot_uid).collect::<Vec<_>>(), [&first.uid, &second.uid]);
+    assert_eq!(versions[1].changes.len(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(SnapshotStore::open(&dir).versions(date).is_err());
+    assert!(!dir.exists());
+  }
+}
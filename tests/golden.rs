@@ -0,0 +1,124 @@
+//! Parses every page in `tests/fixtures/real` and `tests/fixtures/synthetic` and compares the result with
+//! the `.json` file next to it.
+//!
+//! Fixtures are named `<YYYY-MM-DD>_<today|tomorrow>.htm`, the date is the moment the page was saved and
+//! the clock is pinned to it. Run `cargo test --test golden -- --bless` (or set `BLESS=1`) to write the
+//! current output as the expected one.
+//!
+//! `real` holds pages saved from the site as they were served, they are what the parser is checked against.
+//! `synthetic` holds hand-written pages in the college's markup, kept as supplements for cases the saved
+//! pages don't cover.
+//!
+//! Every parsed snapshot is also rendered back into a page with the generator and parsed again, the
+//! result must not change.
+
+use std::{fs, path::Path, process::ExitCode};
+
+use chrono::{Duration, NaiveDate, TimeZone, Utc};
+use maiq_parser::{generator::render_page, source::RawPage, utils::time};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+const SETS: [&str; 2] = ["real", "synthetic"];
+const GROUPS: [&str; 25] = [
+  "Ит1-22", "Са1-21", "Са3-21", "С1-21", "С3-21", "Ир1-21", "Ир3-21", "Ир5-21", "С1-20", "С3-20", "Ип1-20", "Ип3-20", "Ир1-20",
+  "Ир3-20", "Ир5-20", "Кс1-20", "Кс3-20", "Кс5-20", "С1-19", "С3-19", "С1-18", "С3-18", "ЗК1-22", "ЗК1-18", "ЗК1-19",
+];
+
+fn main() -> ExitCode {
+  let bless = std::env::args().any(|x| x == "--bless") || std::env::var("BLESS").map(|x| x == "1").unwrap_or(false);
+  std::env::set_var("GROUPS", GROUPS.join("; "));
+  std::env::set_var("DISCOVER_GROUPS", "false");
+
+  let mut pages = vec![];
+  for set in SETS {
+    let before = pages.len();
+    // A set without pages has no directory, git doesn't keep empty ones
+    if let Ok(entries) = fs::read_dir(Path::new(FIXTURES).join(set)) {
+      pages.extend(
+        entries
+          .map(|entry| entry.unwrap().path())
+          .filter(|path| path.extension().map(|ext| ext == "htm").unwrap_or(false)),
+      );
+    }
+    pages[before..].sort();
+    if pages.len() == before {
+      println!("golden: no pages in tests/fixtures/{}", set);
+    }
+  }
+
+  let mut failed = 0;
+  for page in pages.iter() {
+    let name = page.strip_prefix(FIXTURES).unwrap().to_string_lossy();
+    match check(page, bless) {
+      Ok(()) => println!("golden {} ... ok", name),
+      Err(e) => {
+        println!("golden {} ... FAILED\n{}", name, e);
+        failed += 1;
+      }
+    }
+  }
+
+  println!("\ngolden result: {} passed; {} failed", pages.len() - failed, failed);
+  match failed {
+    0 => ExitCode::SUCCESS,
+    _ => {
+      println!("run `cargo test --test golden -- --bless` if the changes are expected");
+      ExitCode::FAILURE
+    }
+  }
+}
+
+fn check(page: &Path, bless: bool) -> Result<(), String> {
+  let stem = page.file_stem().unwrap().to_string_lossy();
+  let (date, kind) = stem
+    .split_once('_')
+    .ok_or("fixture name must be <YYYY-MM-DD>_<kind>")?;
+  let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|e| e.to_string())?;
+  let saved_at = Utc.from_utc_datetime(&date.and_hms_opt(8, 0, 0).unwrap());
+  time::freeze(Some(saved_at));
+
+  let fallback = match kind {
+    "today" => time::now_date(),
+    _ => time::now_date() + Duration::days(1),
+  };
+  let raw = RawPage::new(fs::read(page).map_err(|e| e.to_string())?);
+  let snapshot = raw.snapshot(fallback).map_err(|e| e.to_string())?;
+  let actual = serde_json::to_string_pretty(&snapshot).map_err(|e| e.to_string())? + "\n";
+  let rendered = render_page(&snapshot).snapshot(fallback).map_err(|e| e.to_string())?;
+  time::freeze(None);
+
+  if rendered.uid != snapshot.uid || rendered.notes != snapshot.notes || rendered.kind != snapshot.kind {
+    let rendered = serde_json::to_string_pretty(&rendered).map_err(|e| e.to_string())? + "\n";
+    return Err(format!("rendered page differs from the original:\n{}", diff(&actual, &rendered).unwrap_or_default()));
+  }
+
+  let golden = page.with_extension("json");
+  if bless {
+    return fs::write(&golden, actual).map_err(|e| e.to_string());
+  }
+
+  let expected = fs::read_to_string(&golden).map_err(|_| format!("{} is missing", golden.display()))?;
+  match diff(&expected, &actual) {
+    None => Ok(()),
+    Some(diff) => Err(diff),
+  }
+}
+
+fn diff(expected: &str, actual: &str) -> Option<String> {
+  let expected = expected.lines().collect::<Vec<&str>>();
+  let actual = actual.lines().collect::<Vec<&str>>();
+  let first = (0..expected.len().max(actual.len())).find(|i| expected.get(*i) != actual.get(*i))?;
+
+  let from = first.saturating_sub(3);
+  let mut out = String::new();
+  for line in expected.iter().take(first).skip(from) {
+    out += &format!("   {}\n", line);
+  }
+  for line in expected.iter().skip(first).take(5) {
+    out += &format!(" - {}\n", line);
+  }
+  for line in actual.iter().skip(first).take(5) {
+    out += &format!(" + {}\n", line);
+  }
+  Some(out)
+}